@@ -0,0 +1,100 @@
+//! Measures how allocating ids scales as more threads contend on the global
+//! counter, and on initializing the same lazy ids. There are no dependencies
+//! (and no nightly `#[bench]`), so this just prints timings; run it with and
+//! without `--features seqcst-counter` or `--features weak-cas-init` to compare
+//! counter orderings and CAS flavors:
+//!
+//! ```text
+//! cargo bench --bench contention
+//! cargo bench --bench contention --features seqcst-counter
+//! cargo bench --bench contention --features weak-cas-init
+//! ```
+use lazy_id::Id;
+use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
+use std::sync::{Arc, Barrier};
+use std::time::{Duration, Instant};
+
+const PER_THREAD: usize = 1_000_000;
+
+// Written to so the loops can't be optimized out.
+static SINK: AtomicU64 = AtomicU64::new(0);
+
+fn run(threads: usize) -> Duration {
+    let barrier = Arc::new(Barrier::new(threads + 1));
+    let handles = (0..threads)
+        .map(|_| {
+            let barrier = barrier.clone();
+            std::thread::spawn(move || {
+                barrier.wait();
+                let folded = (0..PER_THREAD).fold(0, |acc, _| acc ^ Id::new().get());
+                SINK.fetch_xor(folded, Relaxed);
+            })
+        })
+        .collect::<Vec<_>>();
+    // Start the clock first, as the threads may finish before `wait` returns.
+    let start = Instant::now();
+    barrier.wait();
+    for h in handles {
+        h.join().unwrap();
+    }
+    start.elapsed()
+}
+
+const LAZY_PER_THREAD: usize = 100_000;
+
+// Every thread initializes the same lazy ids, in the same order, so most
+// initializations race.
+fn run_lazy(threads: usize) -> Duration {
+    let ids: Arc<Vec<Id>> = Arc::new((0..LAZY_PER_THREAD).map(|_| Id::lazy()).collect());
+    let barrier = Arc::new(Barrier::new(threads + 1));
+    let handles = (0..threads)
+        .map(|_| {
+            let (ids, barrier) = (ids.clone(), barrier.clone());
+            std::thread::spawn(move || {
+                barrier.wait();
+                let folded = ids.iter().fold(0, |acc, id| acc ^ id.get());
+                SINK.fetch_xor(folded, Relaxed);
+            })
+        })
+        .collect::<Vec<_>>();
+    // Start the clock first, as the threads may finish before `wait` returns.
+    let start = Instant::now();
+    barrier.wait();
+    for h in handles {
+        h.join().unwrap();
+    }
+    start.elapsed()
+}
+
+fn main() {
+    let ordering = if cfg!(feature = "seqcst-counter") {
+        "SeqCst"
+    } else {
+        "Relaxed"
+    };
+    let cas = if cfg!(feature = "weak-cas-init") {
+        "weak"
+    } else {
+        "strong"
+    };
+    println!("counter ordering: {}, init cas: {}", ordering, cas);
+    // Warm up.
+    run(1);
+    for &threads in &[1, 2, 4, 8] {
+        let best = (0..5).map(|_| run(threads)).min().unwrap();
+        let ids = (threads * PER_THREAD) as f64;
+        println!(
+            "{:>2} threads: {:>7.2} ns/id",
+            threads,
+            best.as_nanos() as f64 / ids,
+        );
+    }
+    for &threads in &[1, 2, 4, 8] {
+        let best = (0..5).map(|_| run_lazy(threads)).min().unwrap();
+        println!(
+            "{:>2} threads: {:>7.2} ns/lazy init",
+            threads,
+            best.as_nanos() as f64 / LAZY_PER_THREAD as f64,
+        );
+    }
+}
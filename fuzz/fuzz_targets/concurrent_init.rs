@@ -0,0 +1,72 @@
+//! Several threads race to initialize a set of shared lazy `Id`s (which is the
+//! only way to reach the `compare_exchange` loser path), while another thread
+//! builds ids from the fuzzer's bytes via `from_raw_integer`.
+#![no_main]
+use lazy_id::Id;
+use libfuzzer_sys::fuzz_target;
+use std::num::NonZeroU64;
+use std::sync::{Arc, Barrier};
+
+const SHARED: usize = 4;
+const THREADS: usize = 3;
+
+fuzz_target!(|data: &[u8]| {
+    let shared: Arc<Vec<Id>> = Arc::new((0..SHARED).map(|_| Id::lazy()).collect());
+    let barrier = Arc::new(Barrier::new(THREADS + 1));
+    let getters: Vec<_> = (0..THREADS)
+        .map(|t| {
+            let shared = shared.clone();
+            let barrier = barrier.clone();
+            std::thread::spawn(move || {
+                let mut seen = [0u64; SHARED];
+                barrier.wait();
+                for _ in 0..8 {
+                    // Start each thread on a different id so they race on
+                    // different ones first.
+                    for i in 0..SHARED {
+                        let idx = (i + t) % SHARED;
+                        let v = shared[idx].get();
+                        assert_ne!(v, 0);
+                        if seen[idx] == 0 {
+                            seen[idx] = v;
+                        } else {
+                            assert_eq!(seen[idx], v);
+                        }
+                    }
+                }
+                seen
+            })
+        })
+        .collect();
+
+    let bytes = data.to_vec();
+    let raw = std::thread::spawn(move || {
+        barrier.wait();
+        for chunk in bytes.chunks(8) {
+            let mut buf = [0u8; 8];
+            buf[..chunk.len()].copy_from_slice(chunk);
+            let v = u64::from_le_bytes(buf);
+            match NonZeroU64::new(v) {
+                Some(nz) => {
+                    let id = Id::from_raw_integer(nz);
+                    assert_eq!(id.get(), v);
+                    assert_eq!(id.clone(), id);
+                }
+                None => assert!(Id::try_from_raw(v).is_none()),
+            }
+        }
+    });
+    raw.join().unwrap();
+
+    let results: Vec<[u64; SHARED]> = getters.into_iter().map(|t| t.join().unwrap()).collect();
+    for r in &results {
+        assert_eq!(r, &results[0]);
+    }
+    for (id, v) in shared.iter().zip(results[0].iter()) {
+        assert_eq!(id.get(), *v);
+    }
+    let mut distinct = results[0].to_vec();
+    distinct.sort();
+    distinct.dedup();
+    assert_eq!(distinct.len(), SHARED);
+});
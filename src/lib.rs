@@ -18,14 +18,25 @@
 //! the public api.
 //!
 //! Anyway, unlike `lazy_static`/`OnceCell`/the hypothetical `std::lazy`, this
-//! crate is entirely lock free, and only uses a few relaxed atomic operations
-//! to initialize itself on first access (automatically), and the fast path of
-//! reading an already-initialized `Id` is just a single relaxed `load`. This is
+//! crate is entirely lock free, and only uses a few atomic operations to
+//! initialize itself on first access (automatically): a relaxed increment of
+//! the global counter, and a single `Release` compare-exchange to store the
+//! result. The fast path of reading an already-initialized `Id` with
+//! [`Id::get`] is just a single relaxed `load` (only the accessors that hand
+//! out references, like [`Id::as_u64_ref`], need an `Acquire` load). This is
 //! all to say, it's much more efficient than most of the alternatives would be
 //! and more efficient than I had expected it to be.
 #![no_std]
+#![cfg_attr(feature = "nightly", feature(step_trait))]
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
 use core::num::NonZeroU64;
-use core::sync::atomic::{AtomicU64, Ordering::Relaxed};
+#[cfg(feature = "std")]
+use core::sync::atomic::AtomicPtr;
+use core::sync::atomic::{AtomicU64, Ordering, Ordering::Relaxed};
 
 /// A thread-safe lazily-initialized 64-bit ID.
 ///
@@ -42,10 +53,11 @@ use core::sync::atomic::{AtomicU64, Ordering::Relaxed};
 /// `Deref<Target = u64>`, `PartialEq<u64>` (and `u64` has `PartialEq<Id>`),
 /// `Debug`, `Display`, `Default` (same as [`Id::new`])...
 ///
-/// `Id`'s initialization is entirely lock-free and uses only relaxed atomic
-/// operations (nor is anything stronger needed). The fast path of [`Id::get`]
-/// is just a `Relaxed` atomic load, which is the same cost as a non-atomic load
-/// (on platforms that support 64-bit atomics efficiently, anyway).
+/// `Id`'s initialization is entirely lock-free, and uses a single
+/// compare-exchange (with `Release` ordering, so that references from
+/// [`Id::as_u64_ref`] are sound). The fast path of [`Id::get`] is just a
+/// `Relaxed` atomic load, which is the same cost as a non-atomic load (on
+/// platforms that support 64-bit atomics efficiently, anyway).
 ///
 /// # Example
 ///
@@ -81,12 +93,13 @@ use core::sync::atomic::{AtomicU64, Ordering::Relaxed};
 ///
 /// 1. `Id` implements `Clone` by producing other `Id`s with the same numeric
 ///    value. This seems desirable, as it makes the `Id` behave as if it had
-///    been produced eagerly, and more like a normal number.
+///    been produced eagerly, and more like a normal number. If you want the
+///    opposite, wrap it in [`UniqueOnClone`].
 ///
-/// 2. The function [`Id::from_raw_integer`] forces the creation of an `Id` with
-///    a specific numeric value, which may or may not be a value which has been
-///    returned already, and may or may not be one we'll return in the future.
-///    This function should be used with care.
+/// 2. The functions [`Id::from_raw_integer`] and [`Id::from_parts`] force the
+///    creation of an `Id` with a specific numeric value, which may or may not
+///    be a value which has been returned already, and may or may not be one
+///    we'll return in the future. These functions should be used with care.
 ///
 /// It's intentionally okay for unsafe code to assume `Id`s that it creates
 /// through [`Id::new`]/[`Id::lazy`]/[`Id::LAZY_INITIALIZER`] will all have
@@ -106,24 +119,49 @@ use core::sync::atomic::{AtomicU64, Ordering::Relaxed};
 /// can rely on the values returned being unique (so long as they can ensure
 /// that none of them came from `Id::from_raw_integer`).
 ///
+/// The abort message includes the location the id was requested at, as far as
+/// `#[track_caller]` reaches. That's the caller of [`Id::new`] (and the other
+/// constructors), but not of getters like [`Id::get`], which are kept free of
+/// it for speed. Overflow during lazy initialization reports a location inside
+/// `lazy_id` instead.
+///
 /// ### What is `seq=` in the `"{:?}"` output of an `Id`?
 ///
-/// Id debug formats like `"Id(0xhexhexhex; seq=32)"`. The `seq` value is a
-/// monotonically increasing value that can help identify the order `Id`s were
-/// initialized in, but mostly is a vastly more readable number than the real
-/// number, which makes it good for debug output.
+/// Id debug formats like `"Id(0xhexhexhex; seq=32)"` (or with more detail for
+/// `"{:#?}"`). The `seq` value is a monotonically increasing value that can
+/// help identify the order `Id`s were initialized in, but mostly is a vastly
+/// more readable number than the real number, which makes it good for debug
+/// output.
 ///
-/// I may expose a way to convert between `id` values and `seq` values in the
-/// future, let me know if you need it.
+/// The `seq` of an `Id` is available from [`Id::seq`].
 ///
 /// For a little more explanation: By default, ids are mixed somewhat, which
 /// helps discourage people from using them as indexes into arrays or assuming
 /// they're sequential, etc (they aren't — they're just monotonic). It also
 /// might help them be better hash keys, but with a good hash algo it won't
 /// matter.
+///
+/// ### What's the memory layout of an `Id`?
+///
+/// `Id` is guaranteed to have the same layout as `AtomicU64`, which has the
+/// same size and bit validity as a `u64` (8 bytes), and is 8-byte aligned
+/// (which may be stricter than `u64`'s alignment, on some 32-bit targets). So,
+/// it's fine to pass a `*mut Id` to C code expecting a `uint64_t*`, although
+/// the C code must not write to it while Rust code may be accessing it, and
+/// must never write a different value to an initialized `Id` (or zero to an
+/// uninitialized one). A value of 0 means the `Id` is uninitialized.
 #[repr(transparent)]
 pub struct Id(AtomicU64);
 
+// Static assertions for the layout guarantees documented above.
+const _ASSERT_LAYOUT: [(); 1] = [(); (core::mem::size_of::<Id>() == 8
+    && core::mem::size_of::<Id>() == core::mem::size_of::<u64>()
+    && core::mem::align_of::<Id>() == core::mem::align_of::<AtomicU64>()
+    && core::mem::align_of::<Id>() == 8) as usize];
+
+// The mixing constants must be inverses, see `Id::verify_constants`.
+const _ASSERT_BIJECTION: [(); 1] = [(); Id::verify_constants() as usize];
+
 impl Id {
     /// Create an `Id` that will be automatically assigned a value when it's
     /// needed.
@@ -162,6 +200,36 @@ impl Id {
         Self::LAZY_INITIALIZER
     }
 
+    /// Create a lazily-initialized `Id` in an `Arc`.
+    ///
+    /// This is equivalent to `Arc::new(Id::lazy())`, which is fully supported:
+    /// a single lazy `Id` may be shared between any number of threads, and the
+    /// first one to use it will initialize it, without taking a lock. All
+    /// threads will observe the same value.
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::Id;
+    /// let id = Id::lazy_arc();
+    /// let id2 = id.clone();
+    /// let v = std::thread::spawn(move || id2.get()).join().unwrap();
+    /// assert_eq!(id.get(), v);
+    /// ```
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub fn lazy_arc() -> alloc::sync::Arc<Self> {
+        alloc::sync::Arc::new(Self::lazy())
+    }
+
+    /// Create a lazily-initialized `Id` in a `Box`.
+    ///
+    /// This is equivalent to `Box::new(Id::lazy())`.
+    #[cfg(feature = "alloc")]
+    #[inline]
+    pub fn lazy_box() -> alloc::boxed::Box<Self> {
+        alloc::boxed::Box::new(Self::lazy())
+    }
+
     /// Create an `Id` which has been initialized eagerly.
     ///
     /// When you don't need the `const`, use this, as it is more efficient.
@@ -177,8 +245,76 @@ impl Id {
     /// assert_ne!(a, b);
     /// ```
     #[inline]
+    #[track_caller]
     pub fn new() -> Self {
-        Self(AtomicU64::new(Self::next_id().get()))
+        let id = Self::next_id();
+        on_alloc(id);
+        Self(AtomicU64::new(id.get()))
+    }
+
+    /// Create an `Id` which has been initialized eagerly, using `mixer`
+    /// instead of the default mixing multiplier.
+    ///
+    /// This draws from the same global counter as [`Id::new`], so distinct
+    /// calls using the same mixer will never produce duplicates. `Id`s
+    /// produced with different mixers (including the default one) may collide,
+    /// though.
+    ///
+    /// See [`IdMixer`] for more information.
+    #[inline]
+    #[track_caller]
+    pub fn new_mixed(mixer: &IdMixer) -> Self {
+        let id = mixer.mix(next_seq());
+        on_alloc(id);
+        Self(AtomicU64::new(id.get()))
+    }
+
+    /// Create an `Id` which has been initialized eagerly, with a value drawn
+    /// from `allocator` rather than the global counter.
+    ///
+    /// Ids from different allocators never collide with each other, or with
+    /// ids from [`Id::new`]/[`Id::lazy`]. See [`IdAllocator`] for details,
+    /// and [`ScopedId`] for a lazy version of this.
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::{Id, IdAllocator};
+    /// static REQUESTS: IdAllocator = IdAllocator::new();
+    /// let a = Id::new_with_allocator(&REQUESTS);
+    /// let b = Id::new_with_allocator(&REQUESTS);
+    /// assert_ne!(a, b);
+    /// assert!(a.is_older_than(&b));
+    /// ```
+    #[inline]
+    #[track_caller]
+    pub fn new_with_allocator(allocator: &IdAllocator) -> Self {
+        let id = allocator.next_id();
+        on_alloc(id);
+        Self(AtomicU64::new(id.get()))
+    }
+
+    /// Create an `Id` whose value is its sequence number, without any mixing.
+    ///
+    /// This is intended for golden/snapshot tests, where it's nice for id
+    /// values to be small and to match allocation order. The value is drawn
+    /// from the same counter as [`Id::new`], but isn't mixed, so these may
+    /// well collide with normal (mixed) ids — it's similar to
+    /// [`Id::from_raw_integer`] in that respect.
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::Id;
+    /// let a = Id::new_sequential();
+    /// let b = Id::new_sequential();
+    /// assert!(a.get() < b.get());
+    /// ```
+    #[cfg(feature = "test-util")]
+    #[inline]
+    #[track_caller]
+    pub fn new_sequential() -> Self {
+        let seq = next_seq();
+        on_alloc(seq);
+        Self::from_raw_integer(seq)
     }
 
     /// Equivalent to [`Id::lazy()`](Id::lazy) but usable in situations like
@@ -193,17 +329,12 @@ impl Id {
     /// static ARR: [Id; 2] = [Id::lazy(); 2];
     /// ```
     ///
-    /// Using `Id::LAZY_INITIALIZER`, while awkward, works fine (but only in
-    /// rust versions above 1.38.0+)
+    /// Using `Id::LAZY_INITIALIZER`, while awkward, works fine.
     ///
     /// ```
-    /// # #[cfg(not(__older_than_1_38_0))]
-    /// # fn main() {
     /// # use lazy_id::Id;
     /// static ARR: [Id; 2] = [Id::LAZY_INITIALIZER; 2];
     /// assert_ne!(ARR[0], ARR[1]);
-    /// # }
-    /// # #[cfg(__older_than_1_38_0)] fn main() {}
     /// ```
     ///
     /// This API is only present for these sorts of cases, and shouldn't be used
@@ -248,22 +379,356 @@ impl Id {
         if let Some(id) = NonZeroU64::new(self.0.load(Relaxed)) {
             id
         } else {
-            let my_id = self.lazy_init();
+            let (my_id, _) = self.lazy_init(Relaxed, Relaxed);
             debug_assert_eq!(self.0.load(Relaxed), my_id.get());
             my_id
         }
     }
 
+    /// Equivalent to [`Id::get`], but also returns whether this call was the
+    /// one which initialized `self`.
+    ///
+    /// The `bool` is `true` only if this call assigned the value — if `self`
+    /// was already initialized, or another thread won the race to initialize
+    /// it, it's `false`. This is intended for instrumentation, e.g. counting
+    /// how often the cold path is hit. The fast path is the same as `get()`.
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::Id;
+    /// let a = Id::lazy();
+    /// let (v, initialized) = a.observe();
+    /// assert!(initialized);
+    /// assert_eq!(a.observe(), (v, false));
+    /// assert!(!Id::new().observe().1);
+    /// ```
+    #[inline]
+    pub fn observe(&self) -> (u64, bool) {
+        if let Some(id) = NonZeroU64::new(self.0.load(Relaxed)) {
+            (id.get(), false)
+        } else {
+            let (id, initialized) = self.lazy_init(Relaxed, Relaxed);
+            (id.get(), initialized)
+        }
+    }
+
+    /// Equivalent to [`Id::get`], but uses `order` for the load instead of
+    /// `Relaxed`.
+    ///
+    /// This is for users building synchronization on top of the publication of
+    /// an `Id`'s value, most users should just use [`Id::get`]. If `self` is
+    /// uninitialized, it will be initialized as if by
+    /// `self.init_with(order, order)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `order` is `Release` or `AcqRel`, the same as
+    /// [`AtomicU64::load`].
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::Id;
+    /// use core::sync::atomic::Ordering;
+    /// let a = Id::lazy();
+    /// assert_eq!(a.get_with(Ordering::Acquire), a.get());
+    /// ```
+    #[inline]
+    pub fn get_with(&self, order: Ordering) -> u64 {
+        if let Some(id) = NonZeroU64::new(self.0.load(order)) {
+            id.get()
+        } else {
+            self.lazy_init(order, order).0.get()
+        }
+    }
+
+    /// Initialize `self` if needed, using the provided orderings for the
+    /// compare-exchange which performs the initialization, and returns the
+    /// value.
+    ///
+    /// The `success` ordering is used if this call is the one which initializes
+    /// `self`, and `failure` is used otherwise (both when `self` was found to
+    /// already be initialized, and when another thread won the race to
+    /// initialize it). For example, `init_with(Release, Relaxed)` can be used
+    /// to publish writes that happened before the `Id` was initialized, to
+    /// threads which observe its value with [`Id::get_with`]`(Acquire)`.
+    ///
+    /// The `success` ordering is a minimum: the initializing compare-exchange
+    /// is always at least `Release` (so that [`Id::as_u64_ref`] is sound), and
+    /// at least as strong as `failure`. That is, `Relaxed` and `Release` become
+    /// `Release` (or `AcqRel` if `failure` is `Acquire`), `Acquire` becomes
+    /// `AcqRel`, and anything paired with `SeqCst` becomes `SeqCst`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `failure` is `Release` or `AcqRel`, which aren't valid for a
+    /// load. Because of the upgrade, `failure` being stronger than `success`
+    /// is fine, even on compilers before 1.64 (where
+    /// [`AtomicU64::compare_exchange`] would panic for that).
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::Id;
+    /// use core::sync::atomic::Ordering::{Relaxed, Release};
+    /// let a = Id::lazy();
+    /// let v = a.init_with(Release, Relaxed);
+    /// assert_eq!(v.get(), a.get());
+    /// // already initialized, so this just returns the value.
+    /// assert_eq!(a.init_with(Release, Relaxed), v);
+    /// ```
+    #[inline]
+    pub fn init_with(&self, success: Ordering, failure: Ordering) -> NonZeroU64 {
+        if let Some(id) = NonZeroU64::new(self.0.load(failure)) {
+            id
+        } else {
+            self.lazy_init(success, failure).0
+        }
+    }
+
+    /// Block the current thread until `self` has been initialized by another
+    /// thread, and return the value. This never initializes `self` itself.
+    ///
+    /// This is a loop around [`std::thread::yield_now`] — it's only meaningful
+    /// when another thread is going to initialize the `Id` (otherwise it will
+    /// never return), and is not intended for long waits.
+    ///
+    /// The load which observes the value uses `Acquire` ordering, so combined
+    /// with initialization via [`Id::init_with`] using `Release` (or
+    /// stronger), this can be used to hand off data published alongside the
+    /// `Id`.
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::Id;
+    /// use core::sync::atomic::Ordering::{Relaxed, Release};
+    /// let id = Id::lazy_arc();
+    /// let id2 = id.clone();
+    /// let t = std::thread::spawn(move || id2.init_with(Release, Relaxed));
+    /// let v = id.wait_init();
+    /// assert_eq!(v, t.join().unwrap());
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn wait_init(&self) -> NonZeroU64 {
+        loop {
+            if let Some(id) = NonZeroU64::new(self.0.load(Ordering::Acquire)) {
+                return id;
+            }
+            std::thread::yield_now();
+        }
+    }
+
+    /// Returns true if this id's value is `value`, lazily initializing if
+    /// needed.
+    ///
+    /// Equivalent to `id == value` via `PartialEq<u64>`, but as an inherent
+    /// method it's easy to reach for in hot matching loops. Note that if `self`
+    /// is uninitialized, this forces initialization (once), after which the
+    /// comparison is just a load.
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::Id;
+    /// let a = Id::new();
+    /// assert!(a.eq_value(a.get()));
+    /// assert!(!a.eq_value(0));
+    /// ```
+    #[inline]
+    pub fn eq_value(&self, value: u64) -> bool {
+        self.get() == value
+    }
+
+    /// Returns true if this id's value is `value`, lazily initializing if
+    /// needed.
+    ///
+    /// This is [`Id::eq_value`] for a `NonZeroU64`, e.g. something returned
+    /// from [`Id::get_nonzero`].
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::Id;
+    /// let a = Id::lazy();
+    /// let b = Id::lazy();
+    /// let expected = a.get_nonzero();
+    /// assert!(a.eq_nonzero(expected));
+    /// assert!(!b.eq_nonzero(expected));
+    /// ```
+    #[inline]
+    pub fn eq_nonzero(&self, value: NonZeroU64) -> bool {
+        self.get_nonzero() == value
+    }
+
+    /// Returns a [`FrozenId`] holding the value of this id, lazily
+    /// initializing if needed.
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::Id;
+    /// let a = Id::lazy();
+    /// let frozen = a.freeze();
+    /// assert_eq!(a, frozen);
+    /// assert_eq!(frozen.get(), a.get());
+    /// ```
+    #[inline]
+    pub fn freeze(&self) -> FrozenId {
+        FrozenId(self.get_nonzero())
+    }
+
+    /// Returns an [`IdHandle`] holding the value of this id, for passing
+    /// across an FFI boundary. Lazily initializes if needed.
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::Id;
+    /// let a = Id::lazy();
+    /// let h = a.to_handle();
+    /// assert_eq!(h.get(), a.get());
+    /// assert_eq!(h.to_id(), Some(a));
+    /// ```
+    #[inline]
+    pub fn to_handle(&self) -> IdHandle {
+        IdHandle(self.get())
+    }
+
+    /// Deterministically derive a child id from this one and `index`, lazily
+    /// initializing `self` if needed.
+    ///
+    /// For a given parent value and `index`, this always produces the same
+    /// result, which is useful for tree-structured entities (e.g. the two
+    /// children of a node can be `derive(0)` and `derive(1)`). Different
+    /// indices produce different results, with one exception per parent
+    /// value (as there are more `u64`s than nonzero `u64`s).
+    ///
+    /// # Caveats
+    ///
+    /// Derived ids are *not* globally unique — they may collide with
+    /// allocated ids, or with ids derived from a different parent. In that
+    /// sense, this is similar to [`Id::from_raw_integer`].
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::Id;
+    /// let parent = Id::new();
+    /// assert_eq!(parent.derive(0), parent.derive(0));
+    /// assert_ne!(parent.derive(0), parent.derive(1));
+    /// ```
+    #[inline]
+    pub fn derive(&self, index: u64) -> Id {
+        // Mixing with a constant makes the exceptional (zero) index for a
+        // parent much less likely to be one that someone would actually use,
+        // such as its seq. Xor and multiplication by `SEQ2ID` are both
+        // bijections, so different indices give different values.
+        const K: u64 = 0x9e37_79b9_7f4a_7c15;
+        let v = (self.get().rotate_left(32) ^ index ^ K).wrapping_mul(Self::SEQ2ID);
+        match NonZeroU64::new(v) {
+            Some(v) => Self::from_raw_integer(v),
+            // `SEQ2ID` itself would be the first allocated id, so use the mix
+            // of seq `u64::MAX`, which the allocator never hands out.
+            None => Self(AtomicU64::new(Self::SEQ2ID.wrapping_neg())),
+        }
+    }
+
+    /// Returns a reference to the value of this id, lazily initializing if
+    /// needed.
+    ///
+    /// This is what the `Deref` impl uses, and is useful for handing out a
+    /// `&u64` to APIs that need a reference.
+    ///
+    /// # Soundness
+    ///
+    /// Once an `Id` has been initialized, its value is never written to again
+    /// through a shared reference (the only remaining ways to change it
+    /// require `&mut self`), so the returned `&u64` is valid, and will keep
+    /// reading the same value, for the lifetime of `&self`.
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::Id;
+    /// let a = Id::lazy();
+    /// let r: &u64 = a.as_u64_ref();
+    /// assert_eq!(*r, a.get());
+    /// ```
     #[inline]
-    fn get_ref(&self) -> &u64 {
-        // force initialization
-        let _ = self.get();
+    pub fn as_u64_ref(&self) -> &u64 {
+        // Force initialization. This needs to acquire (and the initializing
+        // store needs to release, see `lazy_init`), so that the store which
+        // initialized us happens-before the non-atomic reads through the
+        // reference.
+        if self.0.load(Ordering::Acquire) == 0 {
+            let _ = self.lazy_init(Ordering::AcqRel, Ordering::Acquire);
+        }
         // SAFETY: We've definitely been initialized by now, and so our value
         // will never be written to again (or at least, it no longer has
         // observable interior mutability).
         unsafe { &*(self as *const _ as *const u64) }
     }
 
+    /// Returns a reference to the value of this id if it has already been
+    /// initialized, or `None` if it hasn't, without forcing initialization.
+    ///
+    /// This is useful for init-aware code paths, as going through `Deref`
+    /// (e.g. `&*id`) will always initialize the `Id` if needed.
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::Id;
+    /// let a = Id::lazy();
+    /// assert_eq!(a.try_deref(), None);
+    /// let v = a.get();
+    /// assert_eq!(a.try_deref(), Some(&v));
+    /// ```
+    #[inline]
+    pub fn try_deref(&self) -> Option<&u64> {
+        // Acquire for the same reason as in `as_u64_ref`.
+        if self.0.load(Ordering::Acquire) == 0 {
+            return None;
+        }
+        // SAFETY: Same as `as_u64_ref`: the only way a nonzero value may be
+        // modified is via `&mut self`, which can't happen during the lifetime
+        // of `&self`. The `AtomicU64` has the same in-memory representation as
+        // a `u64`, and we're `repr(transparent)`.
+        Some(unsafe { &*(self as *const _ as *const u64) })
+    }
+
+    /// Returns a pointer to the storage of this id, without initializing it.
+    ///
+    /// This is intended for authors of lock-free data structures who need the
+    /// address for platform-specific atomic operations. The pointee is the
+    /// `AtomicU64` inside the `Id` (which has the same layout as a `u64`, see
+    /// the FAQ), and holds 0 if the `Id` hasn't been initialized yet.
+    ///
+    /// # Safety
+    ///
+    /// Getting the pointer is safe, but using it has the same rules as any
+    /// other pointer to an atomic:
+    ///
+    /// - While other threads may access the `Id`, only access through the
+    ///   pointer atomically (e.g. via `AtomicU64::from_ptr` or inline
+    ///   assembly). A non-atomic read is only sound once you've observed the
+    ///   `Id` as initialized with an `Acquire` load, as [`Id::as_u64_ref`]
+    ///   does.
+    /// - Never write through the pointer to an `Id` which has been
+    ///   initialized, not even 0. Its value must not change while it's only
+    ///   borrowed, since references from [`Id::as_u64_ref`], and things like
+    ///   `Hash`, `Ord` and [`FrozenId`], rely on it staying the same.
+    /// - The only write allowed is initializing an uninitialized `Id`, with a
+    ///   compare-exchange against 0, to a nonzero value that doesn't collide
+    ///   with any existing id. That has the same uniqueness caveats as
+    ///   [`Id::from_raw_integer`].
+    /// - The pointer is only valid for as long as the `Id` is.
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::Id;
+    /// let a = Id::lazy();
+    /// let p = a.as_ptr();
+    /// let v = a.get();
+    /// // SAFETY: No other threads have access to `a`.
+    /// assert_eq!(unsafe { p.read() }, v);
+    /// ```
+    #[inline]
+    pub const fn as_ptr(&self) -> *const u64 {
+        self as *const Self as *const u64
+    }
+
     // TODO: Not sure if this should be public, tbh. Might be confusing.
     /// Equivalent to [`Id::get`], but slightly more efficient for first-time
     /// initialization if you have `&mut` access.
@@ -282,6 +747,7 @@ impl Id {
         }
         let id = Self::next_id();
         *ptr = id.get();
+        on_alloc(id);
         id
     }
 
@@ -290,7 +756,54 @@ impl Id {
     // mult inverse of leet ferris
     const SEQ2ID: u64 = 6848199123282258749;
 
+    /// The seq that the first `Id` allocated by the program gets.
+    ///
+    /// This is 1 by default, but can be set at compile time with the
+    /// `LAZY_ID_START_SEQ` environment variable (for example, so that seqs
+    /// start past some other system's range of low ids). It must be a decimal
+    /// integer between 1 and `i64::MAX` (inclusive), or the build fails.
+    /// Starting higher leaves fewer ids before the counter overflows, which
+    /// still aborts when passing `i64::MAX`.
+    ///
+    /// This only affects the global counter, and doesn't change the value of
+    /// any given seq. Some seq-based APIs do work differently with a high
+    /// base, though:
+    ///
+    /// - [`Id::MAX_COUNT`] shrinks to match the ids left before overflow.
+    /// - Every allocated seq is at least `START_SEQ`, so an [`IdSet`] needs a
+    ///   maximum seq of at least that (see [`IdSet::with_max_seq`]), and uses
+    ///   `START_SEQ / 8` bytes before the first id it stores. If `START_SEQ`
+    ///   is above [`IdSet::DEFAULT_MAX_SEQ`], [`IdSet::new`] can't hold any
+    ///   allocated id, and [`IdCollector`] falls back to its (slower)
+    ///   `BTreeSet` for all of them.
+    pub const START_SEQ: u64 = parse_start_seq(option_env!("LAZY_ID_START_SEQ"));
+
+    /// Returns true if the constants used to mix seqs into id values (and
+    /// back) are valid, which the uniqueness of `Id`s depends on.
+    ///
+    /// Specifically, this checks that the multiplier is odd (so that mixing is
+    /// a bijection), that the unmixing multiplier is its inverse, and that
+    /// [`IdMixer::DEFAULT`] uses them. This always returns true, and the crate
+    /// checks it at compile time, but it's available as a `const fn` for
+    /// startup self-tests, or your own static assertions.
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::Id;
+    /// assert!(Id::verify_constants());
+    /// const _ASSERT: [(); 1] = [(); Id::verify_constants() as usize];
+    /// ```
+    #[inline]
+    pub const fn verify_constants() -> bool {
+        Self::SEQ2ID & 1 == 1
+            && Self::SEQ2ID.wrapping_mul(Self::ID2SEQ) == 1
+            && Self::ID2SEQ.wrapping_mul(Self::SEQ2ID) == 1
+            && IdMixer::DEFAULT.mul == Self::SEQ2ID
+            && IdMixer::DEFAULT.inv == Self::ID2SEQ
+    }
+
     #[inline]
+    #[track_caller]
     fn next_id() -> NonZeroU64 {
         // static assert that the value is odd, proving safety.
         const _ASSERT_ODD: [(); 1] = [(); (Id::SEQ2ID & 1) as usize];
@@ -316,22 +829,61 @@ impl Id {
         }
     }
 
+    // The `bool` is true if this call is the one that initialized `self`.
     #[cold]
-    fn lazy_init(&self) -> NonZeroU64 {
+    #[track_caller]
+    fn lazy_init(&self, success: Ordering, failure: Ordering) -> (NonZeroU64, bool) {
         let id = Self::next_id();
-        // Relaxed is fine here too because we're only interested in the effect
-        // on a single atomic variable. Again, we only care that the ids spit
-        // out by `ALLOC` be distinct, and not that they are in any specific
-        // order, so the two atomic variables don't need synchronization.
-        match self.0.compare_exchange(0, id.get(), Relaxed, Relaxed) {
-            Ok(_) => id,
+        // Relaxed would be fine here as far as the value goes (and is what all
+        // callers other than `init_with` ask for), since we only care that the
+        // ids spit out by `ALLOC` be distinct, and not that they are in any
+        // specific order, so the two atomic variables don't need
+        // synchronization. However, `as_u64_ref` and `try_deref` read the value
+        // non-atomically after an `Acquire` load, which is only free of data
+        // races if the store they observe is a `Release`. This is the cold
+        // path, so we always upgrade. We also make sure the result is at least
+        // as strong as `failure`, since before Rust 1.64, `compare_exchange`
+        // panics otherwise (e.g. for `(Release, Acquire)`).
+        let success = match (success, failure) {
+            (Ordering::SeqCst, _) | (_, Ordering::SeqCst) => Ordering::SeqCst,
+            (Relaxed, Relaxed) | (Ordering::Release, Relaxed) => Ordering::Release,
+            _ => Ordering::AcqRel,
+        };
+        match Self::init_cas(&self.0, id, success, failure) {
+            Ok(_) => {
+                on_alloc(id);
+                (id, true)
+            }
             // Another thread got here first — that's fine, `id` will just
             // go unused.
             Err(e) => {
                 debug_assert!(e != 0);
                 // Safety: the update failed meaning the current value was not
                 // the same.
-                unsafe { core::num::NonZeroU64::new_unchecked(e) }
+                (unsafe { core::num::NonZeroU64::new_unchecked(e) }, false)
+            }
+        }
+    }
+
+    // The CAS used by `lazy_init`. With the `weak-cas-init` feature, this uses
+    // `compare_exchange_weak`, which can be cheaper on LL/SC targets (such as
+    // ARM). A spurious failure there reports the current value as 0, which
+    // isn't a winner we can return, so we retry in that case (and only that
+    // case — any other failure means another thread really did win).
+    #[inline]
+    fn init_cas(
+        cell: &AtomicU64,
+        id: NonZeroU64,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<u64, u64> {
+        if !cfg!(feature = "weak-cas-init") {
+            return cell.compare_exchange(0, id.get(), success, failure);
+        }
+        loop {
+            match cell.compare_exchange_weak(0, id.get(), success, failure) {
+                Err(0) => continue,
+                r => return r,
             }
         }
     }
@@ -362,149 +914,3059 @@ impl Id {
     pub const fn from_raw_integer(id: NonZeroU64) -> Self {
         Self(AtomicU64::new(id.get()))
     }
-}
 
-impl PartialEq for Id {
+    /// Create an id with a specific internal value, or `None` if `id` is zero.
+    ///
+    /// This is [`Id::from_raw_integer`] for a `u64`, and has all the same
+    /// caveats.
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::Id;
+    /// assert_eq!(Id::try_from_raw(400).unwrap().get(), 400);
+    /// assert!(Id::try_from_raw(0).is_none());
+    /// ```
     #[inline]
-    fn eq(&self, o: &Self) -> bool {
-        self.get() == o.get()
+    pub const fn try_from_raw(id: u64) -> Option<Self> {
+        match NonZeroU64::new(id) {
+            Some(id) => Some(Self::from_raw_integer(id)),
+            None => None,
+        }
     }
-}
 
-impl PartialOrd for Id {
+    /// Return this `Id` to the uninitialized state, so that it gets a fresh
+    /// value the next time it's used.
+    ///
+    /// This is useful when recycling objects in a pool: the id doesn't keep a
+    /// stale value, and a new one isn't allocated until it's actually needed.
+    /// It requires `&mut self`, so nothing can be observing the `Id` while
+    /// this happens (and references from [`Id::as_u64_ref`] can't outlive it).
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::Id;
+    /// let mut a = Id::new();
+    /// let old = a.get();
+    /// a.reset_to_lazy();
+    /// assert!(a.try_deref().is_none());
+    /// assert_ne!(a.get(), old);
+    /// ```
     #[inline]
-    fn partial_cmp(&self, o: &Self) -> Option<core::cmp::Ordering> {
-        self.get().partial_cmp(&o.get())
+    pub fn reset_to_lazy(&mut self) {
+        *self.0.get_mut() = 0;
     }
-}
-
-impl Eq for Id {}
 
-impl core::cmp::Ord for Id {
+    /// Consume this `Id`, returning the underlying `AtomicU64`.
+    ///
+    /// This does not initialize the `Id`: the result holds 0 if it was
+    /// uninitialized, and the value otherwise. See [`Id::from_atomic`] for the
+    /// inverse.
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::Id;
+    /// let a = Id::new();
+    /// let v = a.get();
+    /// assert_eq!(a.into_inner().into_inner(), v);
+    /// assert_eq!(Id::lazy().into_inner().into_inner(), 0);
+    /// ```
     #[inline]
-    fn cmp(&self, o: &Self) -> core::cmp::Ordering {
-        self.get().cmp(o)
+    pub const fn into_inner(self) -> AtomicU64 {
+        self.0
     }
-}
 
-impl core::hash::Hash for Id {
+    /// Create an `Id` from an `AtomicU64`, as returned by
+    /// [`Id::into_inner`].
+    ///
+    /// An `AtomicU64` holding 0 becomes an uninitialized, lazy `Id` (which
+    /// will be assigned a fresh value on first use), and any other value
+    /// becomes an `Id` with that value, as if by [`Id::from_raw_integer`] (so
+    /// the same caveats apply).
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::Id;
+    /// use core::sync::atomic::AtomicU64;
+    /// assert_eq!(Id::from_atomic(AtomicU64::new(400)), 400);
+    /// assert!(Id::from_atomic(AtomicU64::new(0)).try_deref().is_none());
+    /// ```
     #[inline]
-    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
-        self.get().hash(state)
+    pub const fn from_atomic(atomic: AtomicU64) -> Self {
+        Self(atomic)
     }
-}
 
-impl PartialEq<u64> for Id {
-    #[inline]
+    /// Register a function to be called each time an `Id` is assigned a fresh
+    /// value, e.g. for emitting a tracing span when ids get created.
+    ///
+    /// The callback is invoked with the newly assigned value from whichever
+    /// thread performed the assignment — for [`Id::new`] this is immediately,
+    /// and for [`Id::lazy`] it's on first use. It is *not* called for clones,
+    /// or for `Id`s created via [`Id::from_raw_integer`]/[`Id::from_parts`].
+    ///
+    /// Only one callback may be registered at a time, and calling this again
+    /// replaces the previous one (use [`Id::clear_on_alloc`] to remove it).
+    /// Registration is lock-free, and may be done from any thread, although a
+    /// concurrent allocation may still see the previous callback (or none).
+    ///
+    /// The callback must not allocate an `Id` itself (including by
+    /// initializing a lazy one), unless it guards against re-entry: the hook
+    /// is called for that allocation too, so an unguarded callback recurses
+    /// until the stack overflows.
+    ///
+    /// # Performance
+    ///
+    /// This adds an atomic load and (once a callback is set) an indirect call
+    /// to the paths that assign a value, that is, `Id::new` and the cold path
+    /// of lazy initialization. Reads of already-initialized `Id`s are
+    /// unaffected.
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::Id;
+    /// # use core::num::NonZeroU64;
+    /// fn trace_alloc(id: NonZeroU64) {
+    ///     println!("allocated id {:#x}", id);
+    /// }
+    /// Id::set_on_alloc(trace_alloc);
+    /// let _ = Id::new(); // prints
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn set_on_alloc(callback: fn(NonZeroU64)) {
+        // Relaxed is fine: the pointer refers to code, not to data that needs
+        // to be published along with it.
+        ON_ALLOC.store(callback as *mut (), Relaxed);
+    }
+
+    /// Remove the callback registered with [`Id::set_on_alloc`], if any.
+    ///
+    /// Like registration, this is lock-free, and a concurrent allocation may
+    /// still see (and call) the previous callback.
+    #[cfg(feature = "std")]
+    pub fn clear_on_alloc() {
+        ON_ALLOC.store(core::ptr::null_mut(), Relaxed);
+    }
+
+    /// Allocate a new unique `Id` (as if by [`Id::new`]), and record `self`
+    /// as its parent, so that [`parent_seq_of`] can find it later.
+    ///
+    /// This is meant for reconstructing allocation lineages in traces, e.g.
+    /// when requests spawn sub-requests. `self` is lazily initialized if
+    /// needed.
+    ///
+    /// # Performance
+    ///
+    /// The links are stored in a global, mutex-protected table that is never
+    /// pruned, so this is much slower than [`Id::new`], and each call leaks a
+    /// few dozen bytes for the lifetime of the program. Avoid it in hot paths
+    /// or unbounded loops.
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::Id;
+    /// let request = Id::new();
+    /// let sub = request.new_after();
+    /// let subsub = sub.new_after();
+    /// assert_eq!(lazy_id::parent_seq_of(&subsub), Some(sub.seq()));
+    /// assert_eq!(lazy_id::parent_seq_of(&sub), Some(request.seq()));
+    /// assert_eq!(lazy_id::parent_seq_of(&request), None);
+    /// ```
+    #[cfg(feature = "std")]
+    #[track_caller]
+    pub fn new_after(&self) -> Id {
+        let parent = self.seq();
+        let child = Id::new();
+        lineage().lock().unwrap().insert(child.seq(), parent);
+        child
+    }
+
+    /// Advance the global epoch used by [`EpochId`], and return the new epoch.
+    ///
+    /// `EpochId`s created after this returns will have the new epoch, and
+    /// their local part restarts from 1. Concurrent calls each advance the
+    /// epoch by one.
+    ///
+    /// This has no effect on `Id` itself, which doesn't have a concept of
+    /// epochs.
+    ///
+    /// # Aborts
+    ///
+    /// There are 2<sup>16</sup> epochs, and we abort if the epoch would
+    /// overflow, for the same reasons we abort on counter overflow.
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::{EpochId, Id};
+    /// let a = EpochId::new();
+    /// let epoch = Id::bump_epoch();
+    /// let b = EpochId::new();
+    /// assert!(a.epoch() < b.epoch());
+    /// assert!(b.epoch() >= epoch);
+    /// ```
+    #[track_caller]
+    pub fn bump_epoch() -> u16 {
+        let mut cur = EPOCH_ALLOC.load(Relaxed);
+        loop {
+            let epoch = cur >> EpochId::LOCAL_BITS;
+            if epoch == u64::from(u16::MAX) {
+                nostd_abort(core::panic::Location::caller());
+            }
+            let next = ((epoch + 1) << EpochId::LOCAL_BITS) | 1;
+            // Relaxed is fine for the same reason as in `next_seq` — we only
+            // care about the effect on this one atomic.
+            match EPOCH_ALLOC.compare_exchange_weak(cur, next, Relaxed, Relaxed) {
+                Ok(_) => return (epoch + 1) as u16,
+                Err(e) => cur = e,
+            }
+        }
+    }
+
+    /// Create an id from a pair of 32-bit halves, with `high` becoming the
+    /// upper 32 bits and `low` the lower 32 bits of the value.
+    ///
+    /// This is a structured alternative to [`Id::from_raw_integer`] for cases
+    /// where you're carving up the 64-bit space yourself (for example, a node
+    /// index in the high bits and a local counter in the low bits). Returns
+    /// `None` if both halves are zero, as zero is reserved to mean
+    /// "uninitialized".
+    ///
+    /// # Caveats
+    ///
+    /// This has the same caveat as [`Id::from_raw_integer`]: the resulting `Id`
+    /// may have a value we use in the future, or have used in the past.
+    ///
+    /// This isn't a `const fn`, so that it doesn't need `NonZeroU64::new` in
+    /// const. For a `static`, use [`ids!`] with `(high << 32) | low` instead.
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::Id;
+    /// let v = Id::from_parts(3, 40).unwrap();
+    /// assert_eq!(v.get(), (3 << 32) | 40);
+    /// assert_eq!((v.high(), v.low()), (3, 40));
+    /// assert!(Id::from_parts(0, 0).is_none());
+    /// ```
+    #[inline]
+    pub fn from_parts(high: u32, low: u32) -> Option<Self> {
+        NonZeroU64::new(((high as u64) << 32) | (low as u64)).map(Self::from_raw_integer)
+    }
+
+    /// Returns the upper 32 bits of this id's value, lazily initializing if
+    /// needed.
+    ///
+    /// This is mostly useful together with [`Id::from_parts`], although it
+    /// works on any `Id`.
+    #[inline]
+    pub fn high(&self) -> u32 {
+        (self.get() >> 32) as u32
+    }
+
+    /// Returns the lower 32 bits of this id's value, lazily initializing if
+    /// needed.
+    ///
+    /// This is mostly useful together with [`Id::from_parts`], although it
+    /// works on any `Id`.
+    #[inline]
+    pub fn low(&self) -> u32 {
+        self.get() as u32
+    }
+
+    /// Returns a well-distributed 32-bit hash of this id's value, lazily
+    /// initializing if needed. This is intended for sharding, e.g.
+    /// `id.shard_hash() % num_shards`.
+    ///
+    /// Unlike the `Hash` impl (which just hashes the `u64`, leaving the quality
+    /// up to the hasher), this finalizes the value and folds the halves
+    /// together, so that the result distributes well even for small shard
+    /// counts. The result only depends on the value, so it's stable across
+    /// runs (for the same value), and equal ids have equal shard hashes.
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::Id;
+    /// let a = Id::new();
+    /// let shard = a.shard_hash() % 16;
+    /// assert_eq!(a.clone().shard_hash() % 16, shard);
+    /// ```
+    #[inline]
+    pub fn shard_hash(&self) -> u32 {
+        // This is the `fmix64` finalizer from MurmurHash3.
+        let mut h = self.get();
+        h ^= h >> 33;
+        h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+        h ^= h >> 33;
+        h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+        h ^= h >> 33;
+        ((h >> 32) ^ h) as u32
+    }
+
+    /// Returns the 64-bit key this id hashes as, lazily initializing if needed.
+    ///
+    /// Every id type in this crate has a `hash_value`, and a `Hash` impl which
+    /// writes exactly that value with a single `write_u64` call, so they can
+    /// share one `HashMap<u64, _>` (and hash identically to their key there).
+    /// For `Id`, this is the value itself, and this will not change.
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::Id;
+    /// let a = Id::new();
+    /// assert_eq!(a.hash_value(), a.get());
+    /// assert_eq!(a.freeze().hash_value(), a.get());
+    /// ```
+    #[inline]
+    pub fn hash_value(&self) -> u64 {
+        self.get()
+    }
+
+    /// Returns the sequence number of this id, lazily initializing if needed.
+    ///
+    /// This is the `seq=` value shown in the `Debug` output, and is
+    /// monotonically increasing in the order `Id`s were initialized in (see
+    /// the FAQ on [`Id`]). For `Id`s which came from [`Id::from_raw_integer`]
+    /// or similar, the result is well-defined, but meaningless.
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::Id;
+    /// let a = Id::new();
+    /// let b = Id::new();
+    /// assert!(a.seq() < b.seq());
+    /// ```
+    #[inline]
+    pub fn seq(&self) -> u64 {
+        self.get().wrapping_mul(Self::ID2SEQ)
+    }
+
+    /// Returns true if `self` was allocated before `other`, lazily
+    /// initializing both if needed (`self` first).
+    ///
+    /// This compares [seqs](Id::seq), which increase in allocation order, so
+    /// use it rather than `<` when you're going for "happened before": the
+    /// numeric order of `Id`s is shuffled, and means nothing.
+    ///
+    /// This only holds for `Id`s allocated by this crate (via [`Id::new`],
+    /// [`Id::lazy`], and so on) during the current run of the program. For
+    /// `Id`s from [`Id::from_raw_integer`] and similar, or ones persisted from
+    /// a previous run, the result is well-defined, but meaningless. An `Id`
+    /// is never older than itself.
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::Id;
+    /// let a = Id::new();
+    /// let b = Id::lazy();
+    /// let c = Id::new();
+    /// // `b` is allocated when it's first used, after `c`.
+    /// assert!(a.is_older_than(&b));
+    /// assert!(c.is_older_than(&b));
+    /// assert!(!b.is_older_than(&a));
+    /// assert!(!a.is_older_than(&a));
+    /// ```
+    #[inline]
+    pub fn is_older_than(&self, other: &Id) -> bool {
+        self.seq() < other.seq()
+    }
+
+    /// Returns `self.seq() / bucket_size`, lazily initializing if needed.
+    ///
+    /// `Id`s allocated around the same time land in the same bucket, which is
+    /// handy for clustering them in logs. See also [`group_by_seq_bucket`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bucket_size` is zero.
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::Id;
+    /// let a = Id::new();
+    /// assert_eq!(a.seq_bucket(1), a.seq());
+    /// assert_eq!(a.seq_bucket(u64::MAX), 0);
+    /// ```
+    #[inline]
+    #[track_caller]
+    pub fn seq_bucket(&self, bucket_size: u64) -> u64 {
+        assert!(bucket_size != 0, "seq_bucket: bucket_size must be nonzero");
+        self.seq() / bucket_size
+    }
+
+    /// Returns [`Id::seq`] as a decimal string, lazily initializing if needed.
+    ///
+    /// Seqs are usually much smaller and more readable than the real value,
+    /// which can make them nicer for logs and exports. Use
+    /// [`Id::from_seq_string`] to convert back.
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::Id;
+    /// let a = Id::new();
+    /// let s = a.to_seq_string();
+    /// assert_eq!(s, a.seq().to_string());
+    /// assert_eq!(Id::from_seq_string(&s), Ok(a));
+    /// ```
+    #[cfg(feature = "alloc")]
+    pub fn to_seq_string(&self) -> alloc::string::String {
+        use alloc::string::ToString;
+        self.seq().to_string()
+    }
+
+    /// Parse a decimal seq (as produced by [`Id::to_seq_string`]) back into an
+    /// `Id` with the same value as the original.
+    ///
+    /// The result is produced as if by [`Id::from_raw_integer`], with all the
+    /// same caveats.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`IdError::Parse`] if `s` isn't a valid `u64`, or
+    /// [`IdError::Zero`] if it is zero.
+    pub fn from_seq_string(s: &str) -> Result<Self, IdError> {
+        let seq = s.parse::<u64>().map_err(|_| IdError::Parse)?;
+        Self::from_seq(seq).ok_or(IdError::Zero)
+    }
+
+    /// Returns the [seq](Id::seq) of this id as a `u32`, or `None` if it
+    /// doesn't fit. Lazily initializes if needed.
+    ///
+    /// Id values are spread over the whole `u64` range, but seqs start at 1
+    /// and are handed out in order, so (absent raw-constructed ids) the seq
+    /// will fit for the first ~4 billion `Id`s allocated by the program.
+    /// This allows storing those compactly, falling back to the full value
+    /// only when needed. Use [`Id::upgrade_from_u32`] to convert back.
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::Id;
+    /// let a = Id::new();
+    /// let small = a.downgrade_to_u32().unwrap();
+    /// assert_eq!(Id::upgrade_from_u32(small), Some(a));
+    /// ```
+    #[inline]
+    pub fn downgrade_to_u32(&self) -> Option<u32> {
+        let seq = self.seq();
+        if seq <= u32::MAX as u64 {
+            Some(seq as u32)
+        } else {
+            None
+        }
+    }
+
+    /// Convert a seq returned by [`Id::downgrade_to_u32`] back into an `Id`
+    /// with the value of the original, or `None` if `seq` is zero.
+    ///
+    /// The result is produced as if by [`Id::from_raw_integer`], with all the
+    /// same caveats.
+    #[inline]
+    pub fn upgrade_from_u32(seq: u32) -> Option<Self> {
+        Self::from_seq(seq as u64)
+    }
+
+    /// Parse the (non-alternate) `Debug` output of an `Id`, such as
+    /// `Id(0x7b; seq=123)`, back into an `Id` with the same value.
+    ///
+    /// The `; seq=...` part is optional, but if present it must match the seq
+    /// of the value. The result is produced as if by [`Id::from_raw_integer`],
+    /// with all the same caveats.
+    ///
+    /// # Errors
+    ///
+    /// - [`IdError::Parse`] if `s` isn't in the format above.
+    /// - [`IdError::Zero`] if the value is zero.
+    /// - [`IdError::SeqMismatch`] if the seq doesn't match the value.
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::{Id, IdError};
+    /// let a = Id::new();
+    /// let s = format!("{:?}", a);
+    /// assert_eq!(Id::from_debug_str(&s), Ok(a.clone()));
+    /// assert_eq!(Id::from_debug_str(&format!("Id({:#x})", a.get())), Ok(a));
+    /// assert_eq!(Id::from_debug_str("Id(0x10; seq=3)"), Err(IdError::SeqMismatch));
+    /// assert_eq!(Id::from_debug_str("Id(10)"), Err(IdError::Parse));
+    /// ```
+    pub fn from_debug_str(s: &str) -> Result<Self, IdError> {
+        fn digits(s: &str, radix: u32) -> Result<u64, IdError> {
+            // `from_str_radix` allows a leading `+`, which we don't want.
+            if s.is_empty() || !s.chars().all(|c| c.is_digit(radix)) {
+                return Err(IdError::Parse);
+            }
+            u64::from_str_radix(s, radix).map_err(|_| IdError::Parse)
+        }
+        let inner = s
+            .strip_prefix("Id(0x")
+            .and_then(|s| s.strip_suffix(')'))
+            .ok_or(IdError::Parse)?;
+        let (hex, seq) = match inner.find("; seq=") {
+            Some(i) => (&inner[..i], Some(&inner[i + "; seq=".len()..])),
+            None => (inner, None),
+        };
+        let id = Self::try_from_raw(digits(hex, 16)?).ok_or(IdError::Zero)?;
+        if let Some(seq) = seq {
+            if digits(seq, 10)? != id.seq() {
+                return Err(IdError::SeqMismatch);
+            }
+        }
+        Ok(id)
+    }
+
+    #[inline]
+    fn from_seq(seq: u64) -> Option<Self> {
+        let seq = NonZeroU64::new(seq)?;
+        Some(Self::from_raw_integer(IdMixer::DEFAULT.mix(seq)))
+    }
+
+    /// Reserve a block of `n` consecutive seqs from the global counter, and
+    /// return an iterator over the `Id`s they correspond to.
+    ///
+    /// This is like calling [`Id::new`] `n` times (and the `Id`s compare and
+    /// order the same way as if you had), but only touches the counter once.
+    /// Unlike `Id::new`, if the block wouldn't fit before the point where the
+    /// counter overflows, this returns an error instead of aborting, and
+    /// doesn't consume any values from the counter. A single huge reservation
+    /// is much more likely to hit the limit than incremental allocation is.
+    ///
+    /// The callback registered with [`Id::set_on_alloc`] is called for each
+    /// `Id` as it's produced by the iterator, rather than all at once.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`IdError::Overflow`] if the counter doesn't have `n` values
+    /// left.
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::{Id, IdError};
+    /// let ids: Vec<Id> = Id::try_reserve(3).unwrap().collect();
+    /// assert!(ids[0].is_older_than(&ids[1]) && ids[1].is_older_than(&ids[2]));
+    /// assert_eq!(Id::try_reserve(u64::MAX).err(), Some(IdError::Overflow));
+    /// ```
+    #[inline]
+    pub fn try_reserve(n: u64) -> Result<ReservedIds, IdError> {
+        let start = reserve_seqs(&ID_ALLOC, n).ok_or(IdError::Overflow)?;
+        Ok(ReservedIds {
+            next: start,
+            end: start + n,
+        })
+    }
+
+    /// Returns the sequence number that the next allocated `Id` would get,
+    /// without allocating it.
+    ///
+    /// Combined with [`IdMixer::DEFAULT`], this can be used to predict the
+    /// value of the next `Id`, e.g. for a log message.
+    ///
+    /// This is only a hint. If other threads are allocating `Id`s, the result
+    /// may already be stale by the time this returns — any number of
+    /// allocations may happen between this call and your next one. Nothing
+    /// should rely on it for correctness.
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::{Id, IdMixer};
+    /// # use core::num::NonZeroU64;
+    /// let seq = Id::peek_next_seq();
+    /// let predicted = IdMixer::DEFAULT.mix(NonZeroU64::new(seq).unwrap());
+    /// // (in a single-threaded program, anyway)
+    /// assert_eq!(Id::new(), predicted);
+    /// ```
+    #[inline]
+    pub fn peek_next_seq() -> u64 {
+        ID_ALLOC.load(Relaxed)
+    }
+
+    /// The largest count accepted by [`Id::assert_count_fits`]. This is half
+    /// (rounded up) of the ids the counter can ever hand out, which leaves the
+    /// other half for the rest of the program. By default, that's
+    /// 2<sup>62</sup>, but it's smaller if [`Id::START_SEQ`] has been raised.
+    pub const MAX_COUNT: u64 = (i64::MAX as u64 - Self::START_SEQ + 2) / 2;
+
+    /// Compile-time check that allocating `n` ids is reasonable, i.e. that
+    /// it's no more than [`Id::MAX_COUNT`].
+    ///
+    /// This is intended for use in a `const` item, such as for a pool of ids
+    /// allocated up-front, so that an absurd size is caught while building
+    /// rather than by the counter overflow abort at runtime (years later). If
+    /// it's called at runtime instead, it panics.
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::Id;
+    /// const POOL_SIZE: u64 = 1 << 20;
+    /// const _: () = Id::assert_count_fits(POOL_SIZE);
+    /// ```
+    ///
+    /// Counts that are too large fail to compile:
+    ///
+    /// ```compile_fail
+    /// # use lazy_id::Id;
+    /// const _: () = Id::assert_count_fits(u64::MAX);
+    /// ```
+    #[inline]
+    #[allow(clippy::no_effect, clippy::unnecessary_operation)]
+    pub const fn assert_count_fits(n: u64) {
+        // See `__id_from_const` for why this is an out-of-bounds index.
+        [()][(n > Self::MAX_COUNT) as usize];
+    }
+
+    /// Returns true if `value` is one that can never be produced by
+    /// [`Id::new`]/[`Id::lazy`] (or an [`IdAllocator`]), so that it's safe to
+    /// use for ids you make yourself.
+    ///
+    /// This is the case when the value's seq (see [`IdMixer::unmix`]) is
+    /// beyond `i64::MAX`, which the global counter never reaches (see the FAQ
+    /// about overflow on [`Id`]), *except* for seqs in `(1 << 63)..(3 << 62)`,
+    /// which are where `IdAllocator`s take their blocks from. So, this returns
+    /// true exactly when the top two bits of the seq are set. 0 isn't a valid
+    /// id value, and returns false.
+    ///
+    /// If you need to reserve raw ids for use with [`Id::from_raw_integer`]
+    /// that will never collide with allocated ones, use values for which this
+    /// returns true.
+    ///
+    /// This only considers the default mixing, and not ids from
+    /// [`Id::new_mixed`] (use [`IdMixer::unmix`] for those).
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::{Id, IdMixer};
+    /// # use core::num::NonZeroU64;
+    /// assert!(!Id::value_is_reserved(Id::new().get()));
+    /// assert!(!Id::value_is_reserved(0));
+    /// // Mixing a seq with the top two bits set gives a value that's never
+    /// // allocated.
+    /// let seq = NonZeroU64::new(3 << 62).unwrap();
+    /// let reserved = Id::from_raw_integer(IdMixer::DEFAULT.mix(seq));
+    /// assert!(Id::value_is_reserved(reserved.get()));
+    /// ```
+    #[inline]
+    pub fn value_is_reserved(value: u64) -> bool {
+        IdMixer::DEFAULT.unmix(value) >> 62 == 0b11
+    }
+
+    /// Best-effort check for whether this id came from
+    /// [`Id::from_raw_integer`] (or similar), rather than the allocator.
+    ///
+    /// This returns true if the seq of this id's value (see [`Id::seq`]) is
+    /// one that the allocator hasn't handed out yet, in which case it almost
+    /// certainly didn't come from it. It can't be perfect:
+    ///
+    /// - There are false negatives: a raw id whose value happens to correspond
+    ///   to an already-allocated seq returns false.
+    /// - Ids from [`Id::new_mixed`] (with a non-default mixer) or
+    ///   `Id::new_sequential` will usually return true.
+    /// - If the id was initialized by another thread without synchronization,
+    ///   we could in theory observe the id value before the counter increment
+    ///   that produced it, and wrongly return true.
+    ///
+    /// Uninitialized `Id`s return false, and this doesn't initialize them. This
+    /// is intended for use in debug assertions.
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::Id;
+    /// assert!(!Id::new().likely_raw_constructed());
+    /// assert!(!Id::lazy().likely_raw_constructed());
+    /// let raw = Id::try_from_raw(400).unwrap();
+    /// assert!(raw.likely_raw_constructed());
+    /// ```
+    #[inline]
+    pub fn likely_raw_constructed(&self) -> bool {
+        match self.try_deref() {
+            Some(&v) => v.wrapping_mul(Self::ID2SEQ) >= Self::peek_next_seq(),
+            None => false,
+        }
+    }
+
+    /// Like [`Id::as_u64_ref`], but when the `checked` feature is enabled,
+    /// returns an error if the value doesn't look like one the allocator has
+    /// handed out.
+    ///
+    /// This is meant as a canary in code that writes to `Id` memory through
+    /// unsafe code (such as FFI): every `u64` is a valid id value, so a
+    /// clobbered `Id` can't be detected for certain, but a random value is
+    /// overwhelmingly likely to have a seq (see [`Id::seq`]) that hasn't been
+    /// allocated yet. The check is the same as the one done by
+    /// [`Id::likely_raw_constructed`], so it has the same false negatives, and
+    /// reports `Id`s from [`Id::from_raw_integer`] and similar as errors. Only
+    /// use it on `Id`s you expect to have come from [`Id::new`]/[`Id::lazy`].
+    ///
+    /// Without the `checked` feature, this always succeeds, and is the same as
+    /// `Ok(self.as_u64_ref())` (a plain load).
+    ///
+    /// Other accessors, such as [`Id::get`] and [`Id::get_nonzero`], don't
+    /// verify anything, even with `checked` and debug assertions. Every value
+    /// has a seq that has either been allocated or hasn't (and so looks raw
+    /// constructed), so there's no check that could catch anything there
+    /// without also rejecting legitimate [`Id::from_raw_integer`] ids. Call
+    /// this in the places where you expect allocated ids instead.
+    ///
+    /// This lazily initializes the `Id` if needed, and `Id`s initialized by
+    /// this call always pass.
+    ///
+    /// # Errors
+    ///
+    /// With the `checked` feature, returns [`IdError::Unallocated`] if the
+    /// value's seq is one the allocator hasn't handed out yet.
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::Id;
+    /// let id = Id::lazy();
+    /// assert_eq!(id.try_get_ref(), Ok(&id.get()));
+    /// let raw = Id::try_from_raw(400).unwrap();
+    /// assert_eq!(raw.try_get_ref().is_err(), cfg!(feature = "checked"));
+    /// ```
+    #[inline]
+    pub fn try_get_ref(&self) -> Result<&u64, IdError> {
+        let r = self.as_u64_ref();
+        if cfg!(feature = "checked") && self.likely_raw_constructed() {
+            return Err(IdError::Unallocated);
+        }
+        Ok(r)
+    }
+}
+
+impl PartialEq for Id {
+    #[inline]
+    fn eq(&self, o: &Self) -> bool {
+        self.get() == o.get()
+    }
+}
+
+impl PartialOrd for Id {
+    #[inline]
+    fn partial_cmp(&self, o: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(o))
+    }
+}
+
+impl Eq for Id {}
+
+impl core::cmp::Ord for Id {
+    #[inline]
+    fn cmp(&self, o: &Self) -> core::cmp::Ordering {
+        self.get().cmp(o)
+    }
+}
+
+/// Writes [`Id::hash_value`] with a single `write_u64`, the same as the `u64`
+/// value does.
+impl core::hash::Hash for Id {
+    #[inline]
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.get().hash(state)
+    }
+}
+
+impl PartialEq<u64> for Id {
+    #[inline]
+    fn eq(&self, o: &u64) -> bool {
+        self.get() == *o
+    }
+}
+
+impl PartialEq<Id> for u64 {
+    #[inline]
+    fn eq(&self, o: &Id) -> bool {
+        *self == o.get()
+    }
+}
+
+// Reference overloads, for comparisons in `filter`/`find` closures and the
+// like. These all delegate to the impls above.
+
+impl PartialEq<&u64> for Id {
+    #[inline]
+    fn eq(&self, o: &&u64) -> bool {
+        *self == **o
+    }
+}
+
+impl PartialEq<Id> for &u64 {
+    #[inline]
+    fn eq(&self, o: &Id) -> bool {
+        **self == *o
+    }
+}
+
+impl PartialEq<&Id> for u64 {
+    #[inline]
+    fn eq(&self, o: &&Id) -> bool {
+        *self == **o
+    }
+}
+
+impl PartialEq<u64> for &Id {
+    #[inline]
+    fn eq(&self, o: &u64) -> bool {
+        **self == *o
+    }
+}
+
+impl PartialEq<&Id> for Id {
+    #[inline]
+    fn eq(&self, o: &&Id) -> bool {
+        *self == **o
+    }
+}
+
+impl PartialEq<Id> for &Id {
+    #[inline]
+    fn eq(&self, o: &Id) -> bool {
+        **self == *o
+    }
+}
+
+impl PartialEq<NonZeroU64> for Id {
+    #[inline]
+    fn eq(&self, o: &NonZeroU64) -> bool {
+        self.get_nonzero() == *o
+    }
+}
+
+impl PartialEq<Id> for NonZeroU64 {
+    #[inline]
+    fn eq(&self, o: &Id) -> bool {
+        *self == o.get_nonzero()
+    }
+}
+
+impl PartialOrd<NonZeroU64> for Id {
+    #[inline]
+    fn partial_cmp(&self, o: &NonZeroU64) -> Option<core::cmp::Ordering> {
+        self.get_nonzero().partial_cmp(o)
+    }
+}
+
+impl PartialOrd<Id> for NonZeroU64 {
+    #[inline]
+    fn partial_cmp(&self, o: &Id) -> Option<core::cmp::Ordering> {
+        self.partial_cmp(&o.get_nonzero())
+    }
+}
+
+impl Clone for Id {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self(AtomicU64::new(self.get()))
+    }
+}
+
+/// Formats as `Id(0xhexhexhex; seq=N)`. This is a fixed format, and ignores
+/// any width, fill, alignment, or precision flags.
+///
+/// The alternate form (`{:#?}`, as used by `dbg!`) is more verbose, and
+/// includes the value in decimal as well, formatting like a struct with
+/// `value_dec`, `value_hex`, and `seq` fields.
+impl core::fmt::Debug for Id {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let v = self.get();
+        if f.alternate() {
+            f.debug_struct("Id")
+                .field("value_dec", &v)
+                .field("value_hex", &format_args!("{:#x}", v))
+                .field("seq", &self.seq())
+                .finish()
+        } else {
+            write!(f, "Id({:#x}; seq={})", v, self.seq())
+        }
+    }
+}
+
+/// Formats the same as the `u64` value, including honoring width, fill,
+/// alignment, and sign flags (e.g. `{:08}` or `{:>12}`).
+impl core::fmt::Display for Id {
+    #[inline]
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Display::fmt(&self.get(), f)
+    }
+}
+
+/// Note that this forces initialization of the `Id`, which may be surprising in
+/// generic code. [`Id::try_deref`] can be used when that isn't desirable.
+impl core::ops::Deref for Id {
+    type Target = u64;
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        self.as_u64_ref()
+    }
+}
+
+impl core::borrow::Borrow<u64> for Id {
+    #[inline]
+    fn borrow(&self) -> &u64 {
+        self
+    }
+}
+
+impl AsRef<u64> for Id {
+    #[inline]
+    fn as_ref(&self) -> &u64 {
+        self
+    }
+}
+
+impl Default for Id {
+    #[inline]
+    fn default() -> Self {
+        Id::new()
+    }
+}
+
+// The by-value and `&mut` conversions can initialize the `Id` without atomic
+// operations (other than the increment of the global counter) via
+// `ensure_init`, while the `&Id` conversion has to go through `get()`.
+
+impl From<Id> for u64 {
+    #[inline]
+    fn from(mut id: Id) -> Self {
+        id.ensure_init().get()
+    }
+}
+
+impl From<&mut Id> for u64 {
+    #[inline]
+    fn from(id: &mut Id) -> Self {
+        id.ensure_init().get()
+    }
+}
+
+impl From<&Id> for u64 {
+    #[inline]
+    fn from(id: &Id) -> Self {
+        id.get()
+    }
+}
+
+impl From<Id> for NonZeroU64 {
+    #[inline]
+    fn from(mut id: Id) -> Self {
+        id.ensure_init()
+    }
+}
+
+/// Returns the value as native-endian bytes, the same as `u64::to_ne_bytes`,
+/// initializing the `Id` if needed.
+///
+/// Native endianness is what you want for in-memory use, but the bytes aren't
+/// portable between platforms. For a fixed byte order, use
+/// `id.get().to_le_bytes()` (or `to_be_bytes`) instead.
+impl From<Id> for [u8; 8] {
+    #[inline]
+    fn from(mut id: Id) -> Self {
+        id.ensure_init().get().to_ne_bytes()
+    }
+}
+
+/// Interprets the bytes as a native-endian `u64`, and creates an `Id` with that
+/// value, as if by [`Id::from_raw_integer`] (with all the same caveats).
+///
+/// Note that this is infallible, so all-zero bytes produce an uninitialized
+/// `Id`, which will get a fresh value when it's first used. Use
+/// [`Id::try_from_raw`] with `u64::from_ne_bytes` if that's not what you want.
+impl From<[u8; 8]> for Id {
+    #[inline]
+    fn from(bytes: [u8; 8]) -> Self {
+        Self(AtomicU64::new(u64::from_ne_bytes(bytes)))
+    }
+}
+
+/// With the `nightly` feature (which requires a nightly compiler), `Id`s may
+/// be iterated over in ranges, like `a..b` for two `Id`s `a` and `b`.
+///
+/// This steps through the raw `u64` values, and is intended for testing, or
+/// for iterating over a space of raw ids — it has nothing to do with
+/// allocation order. Zero is never produced: stepping backwards past `1` (or
+/// forwards past `u64::MAX`) is treated as overflow.
+#[cfg(feature = "nightly")]
+impl core::iter::Step for Id {
+    #[inline]
+    fn steps_between(start: &Self, end: &Self) -> (usize, Option<usize>) {
+        u64::steps_between(&start.get(), &end.get())
+    }
+
+    #[inline]
+    fn forward_checked(start: Self, count: usize) -> Option<Self> {
+        u64::forward_checked(start.get(), count).and_then(Self::try_from_raw)
+    }
+
+    #[inline]
+    fn backward_checked(start: Self, count: usize) -> Option<Self> {
+        u64::backward_checked(start.get(), count).and_then(Self::try_from_raw)
+    }
+}
+
+/// With the `serde` feature, `Id` is serialized as its value. For
+/// human-readable formats (like JSON) this is a decimal string, as many JSON
+/// implementations lose precision on integers above 2<sup>53</sup>. For other
+/// formats, it's a `u64`. Note that serializing an `Id` forces initialization.
+///
+/// Deserialization accepts either form, and produces an `Id` as if by
+/// [`Id::from_raw_integer`] (so the same caveats apply). Zero is rejected.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Id {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(&self.get())
+        } else {
+            serializer.serialize_u64(self.get())
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Id {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct IdVisitor;
+        impl<'de> serde::de::Visitor<'de> for IdVisitor {
+            type Value = Id;
+            fn expecting(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                f.write_str("a nonzero u64, or a string containing one")
+            }
+            fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<Id, E> {
+                Id::try_from_raw(v)
+                    .ok_or_else(|| E::invalid_value(serde::de::Unexpected::Unsigned(v), &self))
+            }
+            fn visit_i64<E: serde::de::Error>(self, v: i64) -> Result<Id, E> {
+                if v > 0 {
+                    self.visit_u64(v as u64)
+                } else {
+                    Err(E::invalid_value(serde::de::Unexpected::Signed(v), &self))
+                }
+            }
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Id, E> {
+                v.parse::<u64>()
+                    .ok()
+                    .and_then(Id::try_from_raw)
+                    .ok_or_else(|| E::invalid_value(serde::de::Unexpected::Str(v), &self))
+            }
+        }
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(IdVisitor)
+        } else {
+            deserializer.deserialize_u64(IdVisitor)
+        }
+    }
+}
+
+/// Serialize and deserialize an `Id` using its [seq](Id::seq), rather than its
+/// value, for use with `#[serde(with = "lazy_id::serde_seq")]`.
+///
+/// The seq is always serialized as a `u64`. Deserializing produces an `Id`
+/// with the same value as the original, as if by [`Id::from_raw_integer`]
+/// (so the same caveats apply).
+///
+/// # Example
+/// ```
+/// # use lazy_id::Id;
+/// #[derive(serde::Serialize, serde::Deserialize)]
+/// struct Thing {
+///     #[serde(with = "lazy_id::serde_seq")]
+///     id: Id,
+/// }
+/// ```
+#[cfg(feature = "serde")]
+pub mod serde_seq {
+    use super::Id;
+
+    /// Serialize `id` as its seq.
+    pub fn serialize<S: serde::Serializer>(id: &Id, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(id.seq())
+    }
+
+    /// Deserialize an `Id` from its seq.
+    pub fn deserialize<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Id, D::Error> {
+        struct SeqVisitor;
+        impl<'de> serde::de::Visitor<'de> for SeqVisitor {
+            type Value = Id;
+            fn expecting(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                f.write_str("a nonzero id seq")
+            }
+            fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<Id, E> {
+                Id::from_seq(v)
+                    .ok_or_else(|| E::invalid_value(serde::de::Unexpected::Unsigned(v), &self))
+            }
+            fn visit_i64<E: serde::de::Error>(self, v: i64) -> Result<Id, E> {
+                if v > 0 {
+                    self.visit_u64(v as u64)
+                } else {
+                    Err(E::invalid_value(serde::de::Unexpected::Signed(v), &self))
+                }
+            }
+        }
+        deserializer.deserialize_u64(SeqVisitor)
+    }
+}
+
+/// The multiplier used to turn sequence numbers into `Id` values.
+///
+/// By default, `Id` values are produced by multiplying a monotonically
+/// increasing sequence number by a fixed odd constant, which mixes them a bit
+/// (see the "What is `seq=`" FAQ on [`Id`]). That should be fine for the vast
+/// majority of uses, but in some cases (for example, some open-addressing hash
+/// tables with a weak hash) it may be desirable to use a different multiplier,
+/// which can be done with an `IdMixer` and [`Id::new_mixed`].
+///
+/// The multiplier must be odd, which ensures that mixing is reversible, and
+/// that a nonzero sequence number never produces a zero id.
+///
+/// Note that the `seq=` value in the `Debug` output of an `Id` always assumes
+/// [`IdMixer::DEFAULT`], so it won't be meaningful for `Id`s produced with
+/// another mixer. Use [`IdMixer::unmix`] for those instead.
+///
+/// # Example
+/// ```
+/// # use lazy_id::{Id, IdMixer};
+/// const MIXER: Option<IdMixer> = IdMixer::new(0x9e37_79b9_7f4a_7c15);
+/// let mixer = MIXER.unwrap();
+/// let a = Id::new_mixed(&mixer);
+/// let b = Id::new_mixed(&mixer);
+/// assert!(mixer.unmix(a.get()) < mixer.unmix(b.get()));
+/// assert!(IdMixer::new(2).is_none());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct IdMixer {
+    mul: u64,
+    inv: u64,
+}
+
+impl IdMixer {
+    /// The mixer used by [`Id::new`] and [`Id::lazy`].
+    pub const DEFAULT: Self = Self {
+        mul: Id::SEQ2ID,
+        inv: Id::ID2SEQ,
+    };
+
+    /// Create a mixer with the provided multiplier, which must be odd. Returns
+    /// `None` if `multiplier` is even.
+    #[inline]
+    pub const fn new(multiplier: u64) -> Option<Self> {
+        if multiplier & 1 == 0 {
+            return None;
+        }
+        // Newton's method for the inverse mod 2^64. Odd `x` is its own inverse
+        // mod 8, so we start with 3 correct bits, and each step doubles that.
+        let mut inv = multiplier;
+        let mut i = 0;
+        while i < 5 {
+            inv = inv.wrapping_mul(2u64.wrapping_sub(multiplier.wrapping_mul(inv)));
+            i += 1;
+        }
+        Some(Self {
+            mul: multiplier,
+            inv,
+        })
+    }
+
+    /// Returns the multiplier that converts a sequence number to an id value.
+    #[inline]
+    pub const fn multiplier(&self) -> u64 {
+        self.mul
+    }
+
+    /// Returns the multiplicative inverse of [`IdMixer::multiplier`], which
+    /// converts an id value back into a sequence number.
+    #[inline]
+    pub const fn inverse(&self) -> u64 {
+        self.inv
+    }
+
+    /// Converts a sequence number to an id value.
+    #[inline]
+    pub fn mix(&self, seq: NonZeroU64) -> NonZeroU64 {
+        let id = seq.get().wrapping_mul(self.mul);
+        debug_assert!(id != 0);
+        // SAFETY: `mul` is odd (checked in `new`), so multiplication by it is
+        // reversible mod 2^64, and the only input mapping to `0` is `0`. See the
+        // comment in `Id::next_id` for the full argument.
+        unsafe { NonZeroU64::new_unchecked(id) }
+    }
+
+    /// Converts an id value back to the sequence number it was produced from.
+    #[inline]
+    pub fn unmix(&self, id: u64) -> u64 {
+        id.wrapping_mul(self.inv)
+    }
+}
+
+impl Default for IdMixer {
+    #[inline]
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// A source of `Id`s which is separate from the global counter.
+///
+/// Different `IdAllocator`s never hand out the same value, and their values
+/// never collide with those from [`Id::new`]/[`Id::lazy`]. This works by
+/// having each allocator claim blocks of 65536 seqs at a time from a shared
+/// pool of blocks, all of which are in `(1 << 63)..(3 << 62)` (a range the
+/// global counter never reaches). That leaves the seqs with both top bits set
+/// free for raw ids (see [`Id::value_is_reserved`]). Within one allocator,
+/// [`Id::seq`] and [`Id::is_older_than`] still reflect the order ids were
+/// handed out in.
+///
+/// Allocators are lock-free, and don't need heap allocation, so they can be
+/// `static`s (which is what [`ScopedId`] requires). Use
+/// [`Id::new_with_allocator`] or [`ScopedId`] to get ids from one.
+///
+/// Because of their large seqs, ids from an `IdAllocator` can't be stored in
+/// an [`IdSet`], and are reported by [`Id::likely_raw_constructed`].
+///
+/// # Aborts
+///
+/// Like the global counter, we abort if the pool of blocks is exhausted. This
+/// would take 2<sup>46</sup> blocks to be claimed, which won't happen in
+/// practice.
+#[derive(Debug, Default)]
+pub struct IdAllocator {
+    // The top 48 bits are one more than the index of the current block (so
+    // that 0 means there's no block yet), and the low 16 are the number of
+    // seqs already handed out from it, which is at most `0xffff` (we skip the
+    // last one, so that the count doesn't need to carry into the block).
+    state: AtomicU64,
+}
+
+impl IdAllocator {
+    const BLOCK_BITS: u32 = 16;
+    const BLOCK_MASK: u64 = (1 << Self::BLOCK_BITS) - 1;
+
+    /// Create a new allocator. This doesn't claim anything until it's first
+    /// used.
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU64::new(0),
+        }
+    }
+
+    #[inline]
+    fn block_seq(block: u64, offset: u64) -> NonZeroU64 {
+        // `block` is below 2^46 (see `next_id`), so bit 62 is always clear.
+        let seq = (1 << 63) | (block << Self::BLOCK_BITS) | offset;
+        // Safety: The top bit is set.
+        unsafe { NonZeroU64::new_unchecked(seq) }
+    }
+
+    #[track_caller]
+    fn next_id(&self) -> NonZeroU64 {
+        let mut state = self.state.load(Relaxed);
+        loop {
+            let (block, used) = (state >> Self::BLOCK_BITS, state & Self::BLOCK_MASK);
+            if block == 0 || used == Self::BLOCK_MASK {
+                break;
+            }
+            // Relaxed is fine for the same reason it is for the global counter.
+            match self
+                .state
+                .compare_exchange_weak(state, state + 1, Relaxed, Relaxed)
+            {
+                Ok(_) => return IdMixer::DEFAULT.mix(Self::block_seq(block - 1, used)),
+                Err(s) => state = s,
+            }
+        }
+        // The current block is used up (or we don't have one), so claim a new
+        // one. The new block belongs to us no matter what, so we take its first
+        // seq even if another thread installs a different block before we can.
+        let block = BLOCK_ALLOC.fetch_add(1, Relaxed);
+        if block >> (62 - Self::BLOCK_BITS) != 0 {
+            nostd_abort(core::panic::Location::caller());
+        }
+        let new = ((block + 1) << Self::BLOCK_BITS) | 1;
+        let _ = self.state.compare_exchange(state, new, Relaxed, Relaxed);
+        IdMixer::DEFAULT.mix(Self::block_seq(block, 0))
+    }
+}
+
+/// A lazily-initialized [`Id`] whose value comes from a specific
+/// [`IdAllocator`], rather than the global counter.
+///
+/// This is the equivalent of [`Id::lazy`] for [`Id::new_with_allocator`]. It
+/// can be used in `static`s, giving them an app-specific id space, as long as
+/// the allocator is itself a `static`. It derefs to the `Id` (initializing it
+/// first), and all accessors go through that, so the value always comes from
+/// the allocator.
+///
+/// # Example
+/// ```
+/// # use lazy_id::{IdAllocator, ScopedId};
+/// static SESSIONS: IdAllocator = IdAllocator::new();
+/// static MAIN_SESSION: ScopedId = ScopedId::lazy(&SESSIONS);
+/// let other = ScopedId::lazy(&SESSIONS);
+/// assert_ne!(MAIN_SESSION.get(), other.get());
+/// ```
+pub struct ScopedId {
+    id: Id,
+    allocator: &'static IdAllocator,
+}
+
+impl ScopedId {
+    /// Create an uninitialized `ScopedId`, which will take its value from
+    /// `allocator` when it's first used.
+    #[inline]
+    pub const fn lazy(allocator: &'static IdAllocator) -> Self {
+        Self {
+            id: Id::LAZY_INITIALIZER,
+            allocator,
+        }
+    }
+
+    /// Returns the allocator this id takes its value from.
+    #[inline]
+    pub fn allocator(&self) -> &'static IdAllocator {
+        self.allocator
+    }
+
+    /// Returns the underlying `Id`, initializing it from the allocator if
+    /// needed.
+    #[inline]
+    pub fn as_id(&self) -> &Id {
+        if self.id.0.load(Relaxed) == 0 {
+            self.init();
+        }
+        &self.id
+    }
+
+    #[cold]
+    #[track_caller]
+    fn init(&self) {
+        let id = self.allocator.next_id();
+        // As in `Id::lazy_init`, losing the race is fine, and the store needs
+        // to be `Release`.
+        if self
+            .id
+            .0
+            .compare_exchange(0, id.get(), Ordering::Release, Relaxed)
+            .is_ok()
+        {
+            on_alloc(id);
+        }
+    }
+}
+
+impl core::ops::Deref for ScopedId {
+    type Target = Id;
+    #[inline]
+    fn deref(&self) -> &Id {
+        self.as_id()
+    }
+}
+
+impl Clone for ScopedId {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            id: self.as_id().clone(),
+            allocator: self.allocator,
+        }
+    }
+}
+
+impl core::fmt::Debug for ScopedId {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Debug::fmt(self.as_id(), f)
+    }
+}
+
+/// A lazily-initialized [`Id`] whose value comes from `provider`, rather than
+/// from the global counter.
+///
+/// This is for cases where id values must come from an external source, such
+/// as a database sequence. When the value is first needed, `provider` is
+/// called, and its result is installed with a CAS. If multiple threads race to
+/// initialize it, each of them might call `provider`, but only one value will
+/// be installed, and all threads see that value — the rest are discarded, so
+/// the provider must be fine with values going unused. If `provider` fails,
+/// the id is left uninitialized, and the error is returned (and the next
+/// access will try again).
+///
+/// Values from the provider are used as-is, so uniqueness is entirely up to
+/// it. In particular, they're treated like [`Id::from_raw_integer`] by the
+/// rest of this crate, and the callback registered with [`Id::set_on_alloc`]
+/// isn't called for them.
+///
+/// # Example
+/// ```
+/// # use lazy_id::LazyWith;
+/// use core::num::NonZeroU64;
+/// use core::sync::atomic::{AtomicU64, Ordering::Relaxed};
+/// static DB_SEQUENCE: AtomicU64 = AtomicU64::new(1000);
+/// fn next_from_db() -> Result<NonZeroU64, &'static str> {
+///     NonZeroU64::new(DB_SEQUENCE.fetch_add(1, Relaxed)).ok_or("db unavailable")
+/// }
+/// static ROW_ID: LazyWith<fn() -> Result<NonZeroU64, &'static str>> = LazyWith::new(next_from_db);
+/// assert!(ROW_ID.try_deref().is_none());
+/// assert_eq!(ROW_ID.try_get().unwrap(), 1000);
+/// assert_eq!(ROW_ID.try_get().unwrap(), 1000);
+/// ```
+pub struct LazyWith<F> {
+    id: Id,
+    provider: F,
+}
+
+impl<F> LazyWith<F> {
+    /// Create an uninitialized id which will get its value from `provider`.
+    #[inline]
+    pub const fn new(provider: F) -> Self {
+        Self {
+            id: Id::LAZY_INITIALIZER,
+            provider,
+        }
+    }
+
+    /// Returns a reference to the value of this id if it has already been
+    /// initialized, or `None` if it hasn't, without calling the provider.
+    #[inline]
+    pub fn try_deref(&self) -> Option<&u64> {
+        self.id.try_deref()
+    }
+}
+
+impl<F, E> LazyWith<F>
+where
+    F: Fn() -> Result<NonZeroU64, E>,
+{
+    /// Returns the underlying `Id`, calling the provider to initialize it if
+    /// needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns the provider's error if it fails, in which case the id remains
+    /// uninitialized.
+    #[inline]
+    pub fn try_get(&self) -> Result<&Id, E> {
+        if self.id.0.load(Relaxed) == 0 {
+            self.init()?;
+        }
+        Ok(&self.id)
+    }
+
+    #[cold]
+    fn init(&self) -> Result<(), E> {
+        let value = (self.provider)()?;
+        // As in `Id::lazy_init`, losing the race is fine, and the store needs
+        // to be `Release`.
+        let _ = self
+            .id
+            .0
+            .compare_exchange(0, value.get(), Ordering::Release, Relaxed);
+        Ok(())
+    }
+}
+
+impl<F> core::fmt::Debug for LazyWith<F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.try_deref() {
+            Some(_) => write!(f, "LazyWith({:?})", self.id),
+            None => f.write_str("LazyWith(<uninit>)"),
+        }
+    }
+}
+
+/// A snapshot of the value of an initialized [`Id`], produced by
+/// [`Id::freeze`].
+///
+/// Unlike `Id`, this is `Copy`, and isn't atomic (and never needs
+/// initialization), which can make it more convenient for passing around
+/// once you know you have a value.
+///
+/// A `FrozenId` compares equal to the `Id` it came from, and hashes
+/// identically to it (both hash the same as their `u64` value, and both
+/// implement `Borrow<u64>`), so they can be used interchangeably as keys.
+///
+/// # Example
+/// ```
+/// # use lazy_id::{FrozenId, Id};
+/// use std::collections::HashSet;
+/// let a = Id::new();
+/// let frozen: FrozenId = a.freeze();
+/// #[allow(clippy::mutable_key_type)]
+/// let set: HashSet<Id> = vec![a].into_iter().collect();
+/// assert!(set.contains(&frozen.get()));
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(transparent)]
+pub struct FrozenId(NonZeroU64);
+
+impl FrozenId {
+    /// Returns the value of this id.
+    #[inline]
+    pub const fn get(self) -> u64 {
+        self.0.get()
+    }
+
+    /// Returns the value of this id as a `NonZeroU64`.
+    #[inline]
+    pub const fn get_nonzero(self) -> NonZeroU64 {
+        self.0
+    }
+
+    /// Returns the 64-bit key this id hashes as, which is its value. See
+    /// [`Id::hash_value`].
+    #[inline]
+    pub const fn hash_value(self) -> u64 {
+        self.0.get()
+    }
+
+    /// Convert back into an (already initialized) [`Id`] with the same
+    /// value.
+    #[inline]
+    pub const fn thaw(self) -> Id {
+        Id::from_raw_integer(self.0)
+    }
+}
+
+// Must match `Id`'s `Hash` impl.
+impl core::hash::Hash for FrozenId {
+    #[inline]
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.get().hash(state)
+    }
+}
+
+impl PartialEq<FrozenId> for Id {
+    #[inline]
+    fn eq(&self, o: &FrozenId) -> bool {
+        self.get_nonzero() == o.0
+    }
+}
+
+impl PartialEq<Id> for FrozenId {
+    #[inline]
+    fn eq(&self, o: &Id) -> bool {
+        self.0 == o.get_nonzero()
+    }
+}
+
+impl PartialEq<u64> for FrozenId {
+    #[inline]
     fn eq(&self, o: &u64) -> bool {
         self.get() == *o
     }
 }
 
-impl PartialEq<Id> for u64 {
+impl PartialEq<FrozenId> for u64 {
+    #[inline]
+    fn eq(&self, o: &FrozenId) -> bool {
+        *self == o.get()
+    }
+}
+
+impl core::fmt::Debug for FrozenId {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let seq = self.get().wrapping_mul(Id::ID2SEQ);
+        write!(f, "FrozenId({:#x}; seq={})", self.get(), seq)
+    }
+}
+
+impl core::fmt::Display for FrozenId {
+    #[inline]
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        core::fmt::Display::fmt(&self.get(), f)
+    }
+}
+
+impl core::borrow::Borrow<u64> for FrozenId {
+    #[inline]
+    fn borrow(&self) -> &u64 {
+        // SAFETY: `NonZeroU64` is `repr(transparent)` over `u64`, and we're
+        // `repr(transparent)` over it.
+        unsafe { &*(self as *const Self as *const u64) }
+    }
+}
+
+impl From<&Id> for FrozenId {
+    #[inline]
+    fn from(id: &Id) -> Self {
+        id.freeze()
+    }
+}
+
+impl From<FrozenId> for Id {
+    #[inline]
+    fn from(id: FrozenId) -> Self {
+        id.thaw()
+    }
+}
+
+impl From<FrozenId> for u64 {
+    #[inline]
+    fn from(id: FrozenId) -> Self {
+        id.get()
+    }
+}
+
+impl From<FrozenId> for NonZeroU64 {
+    #[inline]
+    fn from(id: FrozenId) -> Self {
+        id.0
+    }
+}
+
+/// An atomic cell which is either empty, or holds the value of an [`Id`].
+///
+/// This is useful for publishing ids between threads, e.g. tracking the
+/// highest id seen so far with [`AtomicId::fetch_max`].
+///
+/// Operations which write to the cell use `Release` ordering, and operations
+/// which read from it use `Acquire`, so writes made before publishing an id
+/// are visible to threads that observe it.
+///
+/// # Example
+/// ```
+/// # use lazy_id::{AtomicId, Id};
+/// static LATEST: AtomicId = AtomicId::empty();
+/// assert!(LATEST.load().is_none());
+/// let id = Id::new();
+/// LATEST.store(&id);
+/// assert_eq!(LATEST.load(), Some(id));
+/// ```
+#[repr(transparent)]
+pub struct AtomicId(AtomicU64);
+
+impl AtomicId {
+    /// Create an empty `AtomicId`.
+    #[inline]
+    pub const fn empty() -> Self {
+        Self(AtomicU64::new(0))
+    }
+
+    /// Create an `AtomicId` holding the value of `id`, lazily initializing it
+    /// if needed.
+    #[inline]
+    pub fn new(id: &Id) -> Self {
+        Self(AtomicU64::new(id.get()))
+    }
+
+    /// Returns the id stored in the cell, or `None` if it's empty.
+    #[inline]
+    pub fn load(&self) -> Option<Id> {
+        Id::try_from_raw(self.0.load(Ordering::Acquire))
+    }
+
+    /// Store the value of `id` in the cell, lazily initializing it if needed.
+    #[inline]
+    pub fn store(&self, id: &Id) {
+        self.0.store(id.get(), Ordering::Release)
+    }
+
+    /// Store the value of `id` in the cell if it's numerically larger than
+    /// the current value (or the cell is empty), and return the previous
+    /// contents.
+    ///
+    /// The comparison is by value, the same as `Id`'s `Ord` impl, and not by
+    /// creation order.
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::{AtomicId, Id};
+    /// let max = AtomicId::empty();
+    /// let (a, b) = (Id::new(), Id::new());
+    /// assert_eq!(max.fetch_max(&a), None);
+    /// assert_eq!(max.fetch_max(&b), Some(a.clone()));
+    /// assert_eq!(max.load().unwrap(), core::cmp::max(a, b));
+    /// ```
+    pub fn fetch_max(&self, id: &Id) -> Option<Id> {
+        let new = id.get();
+        let mut cur = self.0.load(Ordering::Acquire);
+        while cur < new {
+            match self
+                .0
+                .compare_exchange_weak(cur, new, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(_) => break,
+                Err(e) => cur = e,
+            }
+        }
+        Id::try_from_raw(cur)
+    }
+
+    /// Store the value of `new` in the cell if it currently holds the value of
+    /// `current`, where `None` means the cell is empty. Returns the previous
+    /// contents, in `Ok` if the store happened and `Err` if it didn't.
+    ///
+    /// Both `Id`s are lazily initialized if needed. Note that this means an
+    /// uninitialized `current` will be given a fresh value, which can't be in
+    /// the cell — pass `None` to compare against an empty cell instead.
+    ///
+    /// The orderings have the same meaning as for
+    /// [`AtomicU64::compare_exchange`], and the same restrictions apply.
+    ///
+    /// # Example
+    /// ```
+    /// # use lazy_id::{AtomicId, Id};
+    /// use core::sync::atomic::Ordering::{AcqRel, Acquire};
+    /// let leader = AtomicId::empty();
+    /// let (a, b) = (Id::new(), Id::new());
+    /// assert_eq!(leader.compare_exchange(None, &a, AcqRel, Acquire), Ok(None));
+    /// assert_eq!(leader.compare_exchange(None, &b, AcqRel, Acquire), Err(Some(a.clone())));
+    /// let prev = leader.compare_exchange(Some(&a), &b, AcqRel, Acquire);
+    /// assert_eq!(prev, Ok(Some(a)));
+    /// assert_eq!(leader.load(), Some(b));
+    /// ```
+    #[inline]
+    pub fn compare_exchange(
+        &self,
+        current: Option<&Id>,
+        new: &Id,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<Option<Id>, Option<Id>> {
+        let current = current.map_or(0, Id::get);
+        self.0
+            .compare_exchange(current, new.get(), success, failure)
+            .map(Id::try_from_raw)
+            .map_err(Id::try_from_raw)
+    }
+}
+
+impl Default for AtomicId {
+    #[inline]
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl core::fmt::Debug for AtomicId {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.load() {
+            Some(id) => write!(f, "AtomicId({:?})", id),
+            None => f.write_str("AtomicId(<empty>)"),
+        }
+    }
+}
+
+/// An id tagged with the epoch it was created in.
+///
+/// The top 16 bits of the value hold the global epoch at the time of creation
+/// (which is advanced by [`Id::bump_epoch`]), and the low 48 bits hold a
+/// counter local to that epoch, which restarts whenever the epoch changes.
+/// This makes it cheap to tell whether two ids were created in the same
+/// epoch.
+///
+/// `EpochId`s use a separate counter from [`Id`], and are created eagerly
+/// (there's no lazy variant). Within a single run of your program, they're
+/// unique in the same way `Id`s are, and their numeric order matches the
+/// order they were created in.
+///
+/// # Aborts
+///
+/// Like [`Id`], we abort on overflow of the local counter (rather than spilling
+/// into the next epoch). Because of how this is detected, an epoch holds
+/// 2<sup>47</sup> - 1 ids, rather than 2<sup>48</sup>.
+///
+/// # Example
+/// ```
+/// # use lazy_id::{EpochId, Id};
+/// let a = EpochId::new();
+/// let b = EpochId::new();
+/// assert_eq!(a.epoch(), b.epoch());
+/// assert!(a.local() < b.local());
+/// Id::bump_epoch();
+/// let c = EpochId::new();
+/// assert_ne!(a.epoch(), c.epoch());
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EpochId(NonZeroU64);
+
+impl EpochId {
+    const LOCAL_BITS: u32 = 48;
+    const LOCAL_MASK: u64 = (1 << Self::LOCAL_BITS) - 1;
+
+    /// Create an `EpochId` in the current epoch.
+    #[inline]
+    #[track_caller]
+    pub fn new() -> Self {
+        let state = EPOCH_ALLOC.fetch_add(1, Relaxed);
+        match epoch_state_to_id(state) {
+            Some(id) => Self(id),
+            None => nostd_abort(core::panic::Location::caller()),
+        }
+    }
+
+    /// Returns the value of this id.
+    #[inline]
+    pub const fn get(self) -> u64 {
+        self.0.get()
+    }
+
+    /// Returns the value of this id as a `NonZeroU64`.
+    #[inline]
+    pub const fn get_nonzero(self) -> NonZeroU64 {
+        self.0
+    }
+
+    /// Returns the 64-bit key this id hashes as, which is its value. See
+    /// [`Id::hash_value`].
+    #[inline]
+    pub const fn hash_value(self) -> u64 {
+        self.0.get()
+    }
+
+    /// Returns the epoch this id was created in.
+    #[inline]
+    pub const fn epoch(self) -> u16 {
+        (self.0.get() >> Self::LOCAL_BITS) as u16
+    }
+
+    /// Returns the part of this id that is local to its epoch. This is never
+    /// zero.
+    #[inline]
+    pub const fn local(self) -> u64 {
+        self.0.get() & Self::LOCAL_MASK
+    }
+}
+
+impl Default for EpochId {
+    #[inline]
+    fn default() -> Self {
+        EpochId::new()
+    }
+}
+
+impl core::fmt::Debug for EpochId {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "EpochId(epoch={}; local={})", self.epoch(), self.local())
+    }
+}
+
+impl From<EpochId> for u64 {
+    #[inline]
+    fn from(id: EpochId) -> Self {
+        id.get()
+    }
+}
+
+impl From<EpochId> for NonZeroU64 {
+    #[inline]
+    fn from(id: EpochId) -> Self {
+        id.0
+    }
+}
+
+/// A wrapper around [`Id`] whose `Clone` impl produces a fresh `Id`, rather
+/// than duplicating the value.
+///
+/// `Id`'s own `Clone` produces an `Id` with the same value, so that it acts
+/// like a normal number. Sometimes though, cloning the containing structure is
+/// supposed to mean "produce a new entity", in which case wrapping the field in
+/// `UniqueOnClone` opts into that, without changing the behavior elsewhere.
+///
+/// # Example
+/// ```
+/// # use lazy_id::{Id, UniqueOnClone};
+/// #[derive(Clone)]
+/// struct Thing {
+///     id: UniqueOnClone,
+/// }
+/// let a = Thing { id: UniqueOnClone(Id::lazy()) };
+/// let b = a.clone();
+/// assert_ne!(a.id, b.id);
+/// ```
+#[derive(Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct UniqueOnClone(pub Id);
+
+impl Clone for UniqueOnClone {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self(Id::new())
+    }
+}
+
+impl From<Id> for UniqueOnClone {
+    #[inline]
+    fn from(id: Id) -> Self {
+        Self(id)
+    }
+}
+
+/// An FFI-safe handle for an [`Id`], with the same ABI as a `u64`.
+///
+/// `Id` is backed by an atomic, which makes it awkward to pass across a C
+/// API. `IdHandle` is a plain, `Copy` wrapper around the value of an `Id`
+/// that is `#[repr(transparent)]`, so it can be used as a `uint64_t` in
+/// `extern "C"` signatures (and tools like `cbindgen` will emit it as a
+/// typedef for one).
+///
+/// The `u64` inside a handle is the raw id value, and [`IdHandle::to_id`]
+/// reconstructs the `Id` as if by [`Id::from_raw_integer`], with the same
+/// caveats. A handle of 0 is never produced by [`Id::to_handle`], so C code
+/// can use it as a "null" handle.
+///
+/// # Example
+/// ```
+/// # use lazy_id::{Id, IdHandle};
+/// extern "C" fn handle_seq(h: IdHandle) -> u64 {
+///     h.to_id().map_or(0, |id| id.seq())
+/// }
+/// let id = Id::lazy();
+/// assert_eq!(handle_seq(id.to_handle()), id.seq());
+/// assert_eq!(handle_seq(IdHandle::from_u64(0)), 0);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct IdHandle(u64);
+
+impl IdHandle {
+    /// Create a handle from a raw `u64`, such as one received from C.
+    #[inline]
+    pub const fn from_u64(v: u64) -> Self {
+        Self(v)
+    }
+
+    /// Returns the raw `u64` inside this handle.
+    #[inline]
+    pub const fn get(self) -> u64 {
+        self.0
+    }
+
+    /// Convert this handle back into an (already initialized) [`Id`], or
+    /// `None` if it's 0.
+    #[inline]
+    pub const fn to_id(self) -> Option<Id> {
+        Id::try_from_raw(self.0)
+    }
+}
+
+impl From<IdHandle> for u64 {
+    #[inline]
+    fn from(h: IdHandle) -> u64 {
+        h.0
+    }
+}
+
+impl From<u64> for IdHandle {
+    #[inline]
+    fn from(v: u64) -> Self {
+        Self(v)
+    }
+}
+
+/// A wrapper around [`Id`] whose `Default` impl is lazy.
+///
+/// `Id`'s `Default` is the same as [`Id::new`], which eagerly takes a value
+/// from the global counter. Using `LazyDefault` for a field instead means
+/// `#[derive(Default)]` produces an [`Id::lazy`], which doesn't take a value
+/// until it's first used.
+///
+/// # Example
+/// ```
+/// # use lazy_id::LazyDefault;
+/// #[derive(Default)]
+/// struct Thing {
+///     id: LazyDefault,
+/// }
+/// let a = Thing::default();
+/// assert!(a.id.try_deref().is_none());
+/// let b = Thing::default();
+/// assert_ne!(*a.id, *b.id);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct LazyDefault(pub Id);
+
+impl LazyDefault {
+    /// Equivalent to `LazyDefault(Id::lazy())`, or `LazyDefault::default()`,
+    /// but usable in const contexts.
+    #[inline]
+    pub const fn new() -> Self {
+        Self(Id::lazy())
+    }
+}
+
+impl Default for LazyDefault {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl core::ops::Deref for LazyDefault {
+    type Target = Id;
+    #[inline]
+    fn deref(&self) -> &Id {
+        &self.0
+    }
+}
+
+impl From<Id> for LazyDefault {
+    #[inline]
+    fn from(id: Id) -> Self {
+        Self(id)
+    }
+}
+
+/// A fixed-size array of independent, lazily-initialized [`Id`]s.
+///
+/// This wraps the `[Id::LAZY_INITIALIZER; N]` pattern, and provides a `Default`
+/// impl (arrays only have one for `N <= 32`, and it would use [`Id::new`]), so
+/// it works with `#[derive(Default)]`. Each element gets its own distinct value
+/// when it's first used.
+///
+/// # Example
+/// ```
+/// # use lazy_id::{Id, LazyIdArray};
+/// static SLOTS: LazyIdArray<4> = LazyIdArray::new();
+/// assert_ne!(SLOTS[0], SLOTS[3]);
+/// let ids: &[Id; 4] = &SLOTS;
+/// assert_eq!(ids.len(), 4);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct LazyIdArray<const N: usize>(pub [Id; N]);
+
+impl<const N: usize> LazyIdArray<N> {
+    /// Create an array of `N` lazy `Id`s. Equivalent to
+    /// `LazyIdArray::default()`, but usable in const contexts.
+    #[inline]
+    pub const fn new() -> Self {
+        Self([Id::LAZY_INITIALIZER; N])
+    }
+}
+
+impl<const N: usize> Default for LazyIdArray<N> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> core::ops::Deref for LazyIdArray<N> {
+    type Target = [Id; N];
+    #[inline]
+    fn deref(&self) -> &[Id; N] {
+        &self.0
+    }
+}
+
+impl<const N: usize> core::ops::DerefMut for LazyIdArray<N> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut [Id; N] {
+        &mut self.0
+    }
+}
+
+impl<I: core::slice::SliceIndex<[Id]>, const N: usize> core::ops::Index<I> for LazyIdArray<N> {
+    type Output = I::Output;
+    #[inline]
+    fn index(&self, index: I) -> &I::Output {
+        &self.0[..][index]
+    }
+}
+
+impl<I: core::slice::SliceIndex<[Id]>, const N: usize> core::ops::IndexMut<I> for LazyIdArray<N> {
+    #[inline]
+    fn index_mut(&mut self, index: I) -> &mut I::Output {
+        &mut self.0[..][index]
+    }
+}
+
+impl<const N: usize> From<[Id; N]> for LazyIdArray<N> {
+    #[inline]
+    fn from(ids: [Id; N]) -> Self {
+        Self(ids)
+    }
+}
+
+/// A wrapper around [`Id`] which compares and sorts by [seq](Id::seq) (that
+/// is, by allocation order) rather than by value.
+///
+/// `Id`'s `Ord` compares the (shuffled) values, which is fine for ordered maps,
+/// but means the order is otherwise meaningless. Using `SeqKey` as the key of a
+/// `BTreeMap` instead means the map iterates in creation order, and supports
+/// range queries like "everything created after this id".
+///
+/// `SeqKey` doesn't implement `Borrow<Id>`, since the two order differently,
+/// so lookups need a `SeqKey`.
+///
+/// # Example
+/// ```
+/// # use lazy_id::{Id, SeqKey};
+/// use std::collections::BTreeMap;
+/// use std::ops::Bound::{Excluded, Unbounded};
+/// let ids: Vec<Id> = (0..5).map(|_| Id::new()).collect();
+/// let map: BTreeMap<SeqKey, usize> = ids
+///     .iter()
+///     .enumerate()
+///     .map(|(i, id)| (SeqKey(id.clone()), i))
+///     .collect();
+/// let after: Vec<usize> = map
+///     .range((Excluded(SeqKey(ids[2].clone())), Unbounded))
+///     .map(|(_, &i)| i)
+///     .collect();
+/// assert_eq!(after, [3, 4]);
+/// ```
+#[derive(Clone, Debug, Default)]
+#[repr(transparent)]
+pub struct SeqKey(pub Id);
+
+impl PartialEq for SeqKey {
+    #[inline]
+    fn eq(&self, o: &Self) -> bool {
+        self.0.seq() == o.0.seq()
+    }
+}
+
+impl Eq for SeqKey {}
+
+impl PartialOrd for SeqKey {
+    #[inline]
+    fn partial_cmp(&self, o: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(o))
+    }
+}
+
+impl Ord for SeqKey {
+    #[inline]
+    fn cmp(&self, o: &Self) -> core::cmp::Ordering {
+        self.0.seq().cmp(&o.0.seq())
+    }
+}
+
+impl core::hash::Hash for SeqKey {
+    #[inline]
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.0.seq().hash(state)
+    }
+}
+
+impl From<Id> for SeqKey {
+    #[inline]
+    fn from(id: Id) -> Self {
+        Self(id)
+    }
+}
+
+impl From<SeqKey> for Id {
+    #[inline]
+    fn from(k: SeqKey) -> Self {
+        k.0
+    }
+}
+
+impl AsRef<Id> for SeqKey {
+    #[inline]
+    fn as_ref(&self) -> &Id {
+        &self.0
+    }
+}
+
+/// Types which can be viewed as an id value, for APIs which want to accept
+/// "anything id-like".
+///
+/// This is implemented for [`Id`] (lazily initializing it if needed), the
+/// other id types in this crate, `u64`, and `NonZeroU64`, as well as for
+/// references to any of these.
+///
+/// For `Id`s and the other id types, `as_id` never returns 0. A plain `u64`
+/// is passed through as-is, so it's up to the caller whether 0 is meaningful.
+///
+/// # Example
+/// ```
+/// # use lazy_id::{AsId, Id};
+/// fn register(x: impl AsId) -> u64 {
+///     x.as_id()
+/// }
+/// let id = Id::lazy();
+/// let v = register(&id);
+/// assert_eq!(register(v), v);
+/// assert_eq!(register(id.get_nonzero()), v);
+/// assert_eq!(register(id), v);
+/// ```
+pub trait AsId {
+    /// Returns the id value.
+    fn as_id(&self) -> u64;
+}
+
+impl AsId for Id {
+    #[inline]
+    fn as_id(&self) -> u64 {
+        self.get()
+    }
+}
+
+impl AsId for FrozenId {
+    #[inline]
+    fn as_id(&self) -> u64 {
+        self.get()
+    }
+}
+
+impl AsId for EpochId {
+    #[inline]
+    fn as_id(&self) -> u64 {
+        self.get()
+    }
+}
+
+impl AsId for u64 {
+    #[inline]
+    fn as_id(&self) -> u64 {
+        *self
+    }
+}
+
+impl AsId for NonZeroU64 {
+    #[inline]
+    fn as_id(&self) -> u64 {
+        self.get()
+    }
+}
+
+impl<T: AsId + ?Sized> AsId for &T {
     #[inline]
-    fn eq(&self, o: &Id) -> bool {
-        *self == o.get()
+    fn as_id(&self) -> u64 {
+        (**self).as_id()
     }
 }
 
-impl Clone for Id {
+/// A seedable, deterministic source of `Id`s, intended for tests.
+///
+/// Unlike [`Id::new`], this doesn't touch the global counter, and produces the
+/// same sequence of values for the same seed on every run, which makes it
+/// handy for reproducible test fixtures. Internally it's a simple 64-bit LCG,
+/// with each output passed to [`Id::from_raw_integer`].
+///
+/// # Caveats
+///
+/// These ids are for tests only. They may collide with ids produced by
+/// [`Id::new`]/[`Id::lazy`], and the sequences produced by different seeds may
+/// overlap with each other. A single `SeededIds` won't repeat itself until it
+/// has produced 2<sup>64</sup> - 1 values.
+///
+/// # Example
+/// ```
+/// # use lazy_id::SeededIds;
+/// let mut a = SeededIds::new(1234);
+/// let mut b = SeededIds::new(1234);
+/// assert_eq!(a.next(), b.next());
+/// assert_ne!(a.next(), a.next());
+/// ```
+#[derive(Clone, Debug)]
+pub struct SeededIds {
+    state: u64,
+}
+
+impl SeededIds {
+    // Knuth's MMIX LCG constants.
+    const MUL: u64 = 6364136223846793005;
+    const INC: u64 = 1442695040888963407;
+
+    /// Create a generator which will produce a fixed sequence of `Id`s
+    /// determined by `seed`.
     #[inline]
-    fn clone(&self) -> Self {
-        Self(AtomicU64::new(self.get()))
+    pub const fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Returns the next `Id` in this generator's sequence.
+    #[inline]
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Id {
+        loop {
+            self.state = self.state.wrapping_mul(Self::MUL).wrapping_add(Self::INC);
+            // The LCG has full period, so it hits zero exactly once per cycle,
+            // in which case we just take the next value.
+            if let Some(v) = NonZeroU64::new(self.state) {
+                return Id::from_raw_integer(v);
+            }
+        }
     }
 }
 
-impl core::fmt::Debug for Id {
-    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        let v = self.get();
-        write!(f, "Id({:#x}; seq={})", v, v.wrapping_mul(Self::ID2SEQ))
+impl Iterator for SeededIds {
+    type Item = Id;
+    #[inline]
+    fn next(&mut self) -> Option<Id> {
+        Some(SeededIds::next(self))
     }
 }
 
-impl core::fmt::Display for Id {
+/// Returns the [seq](Id::seq) of the `Id` that `id` was created from via
+/// [`Id::new_after`], or `None` if it wasn't created that way.
+///
+/// `id` is lazily initialized if needed (which means it will be `None`). Since
+/// links are keyed by seq, this also finds the parent for `id`'s clones, or
+/// for `Id`s reconstructed with [`Id::from_raw_integer`].
+///
+/// # Example
+/// ```
+/// # use lazy_id::Id;
+/// let parent = Id::new();
+/// let child = parent.new_after();
+/// let copy = Id::from_raw_integer(child.get_nonzero());
+/// assert_eq!(lazy_id::parent_seq_of(&copy), Some(parent.seq()));
+/// ```
+#[cfg(feature = "std")]
+pub fn parent_seq_of(id: &Id) -> Option<u64> {
+    let seq = id.seq();
+    lineage().lock().unwrap().get(&seq).copied()
+}
+
+/// Summary statistics over the [seqs](Id::seq) of some `Id`s, returned by
+/// [`seq_stats`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SeqStats {
+    /// The smallest seq.
+    pub min: u64,
+    /// The largest seq.
+    pub max: u64,
+    /// The number of `Id`s.
+    pub count: usize,
+}
+
+impl SeqStats {
+    /// Returns `max - min`, a rough measure of how spread out in time the
+    /// `Id`s were allocated.
     #[inline]
-    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        self.get().fmt(f)
+    pub fn span(&self) -> u64 {
+        self.max - self.min
     }
 }
 
-impl core::ops::Deref for Id {
-    type Target = u64;
+/// Returns the min and max [seq](Id::seq) of `ids`, along with how many there
+/// are, or `None` if `ids` is empty. Lazily initializes the `Id`s if needed.
+///
+/// This uses seqs, rather than the values themselves, so (as long as the `Id`s
+/// came from [`Id::new`]/[`Id::lazy`]) it reflects allocation order.
+/// Duplicates are counted each time they appear.
+///
+/// # Example
+/// ```
+/// # use lazy_id::Id;
+/// let ids: Vec<Id> = (0..10).map(|_| Id::new()).collect();
+/// let stats = lazy_id::seq_stats(&ids).unwrap();
+/// assert_eq!((stats.min, stats.max), (ids[0].seq(), ids[9].seq()));
+/// assert_eq!(stats.count, 10);
+/// assert_eq!(lazy_id::seq_stats(&[]), None);
+/// ```
+pub fn seq_stats(ids: &[Id]) -> Option<SeqStats> {
+    let (first, rest) = ids.split_first()?;
+    let seq = first.seq();
+    let mut stats = SeqStats {
+        min: seq,
+        max: seq,
+        count: ids.len(),
+    };
+    for id in rest {
+        let seq = id.seq();
+        stats.min = stats.min.min(seq);
+        stats.max = stats.max.max(seq);
+    }
+    Some(stats)
+}
+
+/// Returns clones of `ids`, sorted by [`Id::seq`] (that is, the order in which
+/// they were initialized) rather than by numeric value.
+///
+/// This is mostly useful for debug output, where the mixed order `Id`s sort in
+/// by default is fairly meaningless.
+///
+/// # Example
+/// ```
+/// # use lazy_id::Id;
+/// let a = Id::new();
+/// let b = Id::new();
+/// let c = Id::new();
+/// let sorted = lazy_id::sorted_by_seq(&[c.clone(), a.clone(), b.clone()]);
+/// assert_eq!(sorted, [a, b, c]);
+/// ```
+#[cfg(feature = "alloc")]
+pub fn sorted_by_seq(ids: &[Id]) -> alloc::vec::Vec<Id> {
+    let mut v = ids.to_vec();
+    v.sort_by_key(Id::seq);
+    v
+}
+
+/// Groups `ids` by [`Id::seq_bucket`], returning `(bucket, ids)` pairs sorted
+/// by bucket. Within a bucket, the `Id`s are in the order they appear in
+/// `ids`.
+///
+/// This is intended for debug output, to cluster `Id`s allocated around the
+/// same time.
+///
+/// # Panics
+///
+/// Panics if `bucket_size` is zero.
+///
+/// # Example
+/// ```
+/// # use lazy_id::Id;
+/// let ids: Vec<Id> = (0..3).map(|_| Id::new()).collect();
+/// let groups = lazy_id::group_by_seq_bucket(&ids, u64::MAX);
+/// assert_eq!(groups, [(0, ids)]);
+/// ```
+#[cfg(feature = "alloc")]
+#[track_caller]
+pub fn group_by_seq_bucket(
+    ids: &[Id],
+    bucket_size: u64,
+) -> alloc::vec::Vec<(u64, alloc::vec::Vec<Id>)> {
+    assert!(
+        bucket_size != 0,
+        "group_by_seq_bucket: bucket_size must be nonzero"
+    );
+    let mut groups = alloc::collections::BTreeMap::<u64, alloc::vec::Vec<Id>>::new();
+    for id in ids {
+        groups
+            .entry(id.seq_bucket(bucket_size))
+            .or_default()
+            .push(id.clone());
+    }
+    groups.into_iter().collect()
+}
+
+/// Produces an array of `Id`s with the provided raw values, as if by
+/// [`Id::from_raw_integer`].
+///
+/// The values must be constant expressions, and this is usable in `const` and
+/// `static` initializers. A value of `0` is rejected at compile time.
+///
+/// This has all the same caveats around uniqueness as
+/// [`Id::from_raw_integer`].
+///
+/// # Example
+/// ```
+/// static TABLE: [lazy_id::Id; 3] = lazy_id::ids![1, 2, 0x30];
+/// assert_eq!(TABLE[2].get(), 0x30);
+/// ```
+///
+/// Zero is not allowed:
+///
+/// ```compile_fail
+/// let _ = lazy_id::ids![1, 0];
+/// ```
+#[macro_export]
+macro_rules! ids {
+    ($($v:expr),* $(,)?) => {
+        [$({
+            const ID: $crate::Id = $crate::__id_from_const($v);
+            ID
+        }),*]
+    };
+}
+
+#[doc(hidden)]
+pub const fn __id_from_const(v: u64) -> Id {
+    // Out-of-bounds indexing is how we turn `0` into a compile error, as the
+    // MSRV doesn't support panicking in const.
+    #[allow(clippy::no_effect, clippy::unnecessary_operation)]
+    [()][(v == 0) as usize];
+    match Id::try_from_raw(v) {
+        Some(id) => id,
+        None => Id::LAZY_INITIALIZER,
+    }
+}
+
+/// An iterator over a block of `Id`s reserved by [`Id::try_reserve`].
+///
+/// Seqs in the block which aren't iterated over are simply never used.
+#[derive(Clone, Debug)]
+pub struct ReservedIds {
+    next: u64,
+    end: u64,
+}
+
+impl ReservedIds {
+    /// Returns the number of `Id`s left in the block.
     #[inline]
-    fn deref(&self) -> &Self::Target {
-        self.get_ref()
+    pub fn len(&self) -> u64 {
+        self.end - self.next
+    }
+
+    /// Returns true if there are no `Id`s left in the block.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.next == self.end
     }
 }
 
-impl core::borrow::Borrow<u64> for Id {
+impl Iterator for ReservedIds {
+    type Item = Id;
+
     #[inline]
-    fn borrow(&self) -> &u64 {
-        self
+    fn next(&mut self) -> Option<Id> {
+        if self.next == self.end {
+            return None;
+        }
+        // Safety: `reserve_seqs` never hands out 0.
+        let seq = unsafe { NonZeroU64::new_unchecked(self.next) };
+        self.next += 1;
+        let id = IdMixer::DEFAULT.mix(seq);
+        on_alloc(id);
+        Some(Id::from_raw_integer(id))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.len();
+        if n <= usize::MAX as u64 {
+            (n as usize, Some(n as usize))
+        } else {
+            (usize::MAX, None)
+        }
     }
 }
 
-impl AsRef<u64> for Id {
+/// A set of `Id`s, stored as a bitset indexed by [seq](Id::seq).
+///
+/// Allocated seqs are dense (1, 2, 3, ...), even though the id values are
+/// not, so for ids which came from [`Id::new`]/[`Id::lazy`] this is much more
+/// compact than a `HashSet<Id>` — roughly one bit per id allocated so far in
+/// the program, rather than 8+ bytes per entry.
+///
+/// The flip side is that memory use is proportional to the largest seq
+/// inserted, so inserting an id from [`Id::from_raw_integer`] (whose seq is
+/// likely huge) would allocate a huge bitset. To guard against this, the set
+/// has a maximum seq (which can be configured with [`IdSet::with_max_seq`]),
+/// and inserting an id whose seq is larger panics. If [`Id::START_SEQ`] has
+/// been raised, the maximum needs to be at least that high, see its docs.
+///
+/// # Example
+/// ```
+/// # use lazy_id::{Id, IdSet};
+/// let (a, b) = (Id::new(), Id::new());
+/// let mut set = IdSet::new();
+/// assert!(set.insert(&a));
+/// assert!(!set.insert(&a));
+/// assert!(set.contains(&a) && !set.contains(&b));
+/// assert!(set.remove(&a));
+/// assert!(set.is_empty());
+/// ```
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug)]
+pub struct IdSet {
+    bits: alloc::vec::Vec<u64>,
+    len: usize,
+    max_seq: u64,
+}
+
+#[cfg(feature = "alloc")]
+impl IdSet {
+    /// The default maximum seq, which limits the bitset to 8MiB.
+    pub const DEFAULT_MAX_SEQ: u64 = 1 << 26;
+
+    /// Create an empty set, with a maximum seq of [`IdSet::DEFAULT_MAX_SEQ`].
     #[inline]
-    fn as_ref(&self) -> &u64 {
-        self
+    pub const fn new() -> Self {
+        Self::with_max_seq(Self::DEFAULT_MAX_SEQ)
+    }
+
+    /// Create an empty set which allows inserting ids with seqs up to and
+    /// including `max_seq`. The bitset may grow to `max_seq / 8` bytes.
+    #[inline]
+    pub const fn with_max_seq(max_seq: u64) -> Self {
+        Self {
+            bits: alloc::vec::Vec::new(),
+            len: 0,
+            max_seq,
+        }
+    }
+
+    #[inline]
+    fn split(seq: u64) -> (usize, u64) {
+        ((seq / 64) as usize, 1 << (seq % 64))
+    }
+
+    /// Add `id` to the set, lazily initializing it if needed. Returns true if
+    /// it wasn't already present.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the seq of `id` is larger than the maximum seq for this set.
+    #[track_caller]
+    pub fn insert(&mut self, id: &Id) -> bool {
+        let seq = id.seq();
+        assert!(
+            seq <= self.max_seq,
+            "IdSet: seq {} is above the maximum of {} (was it from `Id::from_raw_integer`?)",
+            seq,
+            self.max_seq,
+        );
+        let (word, bit) = Self::split(seq);
+        if word >= self.bits.len() {
+            self.bits.resize(word + 1, 0);
+        }
+        let present = self.bits[word] & bit != 0;
+        self.bits[word] |= bit;
+        if !present {
+            self.len += 1;
+        }
+        !present
+    }
+
+    /// Returns true if `id` is in the set, lazily initializing it if needed.
+    #[inline]
+    pub fn contains(&self, id: &Id) -> bool {
+        let (word, bit) = Self::split(id.seq());
+        self.bits.get(word).map_or(false, |w| w & bit != 0)
+    }
+
+    /// Remove `id` from the set, lazily initializing it if needed. Returns
+    /// true if it was present.
+    pub fn remove(&mut self, id: &Id) -> bool {
+        let (word, bit) = Self::split(id.seq());
+        match self.bits.get_mut(word) {
+            Some(w) if *w & bit != 0 => {
+                *w &= !bit;
+                self.len -= 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the number of ids in the set.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the set contains no ids.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Iterate over the ids in the set, in seq order.
+    pub fn iter(&self) -> impl Iterator<Item = Id> + '_ {
+        self.bits.iter().enumerate().flat_map(|(i, &word)| {
+            (0..64u64).filter_map(move |b| {
+                if word & (1 << b) == 0 {
+                    return None;
+                }
+                Id::from_seq(i as u64 * 64 + b)
+            })
+        })
     }
 }
 
-impl Default for Id {
+// Sets are equal if they have the same members, regardless of their maximum
+// seqs, or of any trailing zero words left behind by `remove`.
+#[cfg(feature = "alloc")]
+impl PartialEq for IdSet {
+    fn eq(&self, o: &Self) -> bool {
+        fn trimmed(bits: &[u64]) -> &[u64] {
+            let end = bits.iter().rposition(|&w| w != 0).map_or(0, |i| i + 1);
+            &bits[..end]
+        }
+        self.len == o.len && trimmed(&self.bits) == trimmed(&o.bits)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Eq for IdSet {}
+
+#[cfg(feature = "alloc")]
+impl Default for IdSet {
     #[inline]
     fn default() -> Self {
-        Id::new()
+        Self::new()
     }
 }
 
-impl From<Id> for u64 {
+/// Collects `Id`s, discarding duplicates, and keeping them in the order they
+/// were first seen.
+///
+/// Allocated `Id`s (those with small [seqs](Id::seq)) are deduplicated with an
+/// [`IdSet`], so for the common case this uses much less memory than a
+/// `HashSet<Id>` would. `Id`s with seqs too large for that set (such as most
+/// from [`Id::from_raw_integer`], or all of them if [`Id::START_SEQ`] is above
+/// [`IdSet::DEFAULT_MAX_SEQ`]) go in a `BTreeSet` instead.
+///
+/// # Example
+/// ```
+/// # use lazy_id::{Id, IdCollector};
+/// let (a, b) = (Id::new(), Id::new());
+/// let raw = Id::try_from_raw(400).unwrap();
+/// let all = vec![b.clone(), a.clone(), raw.clone(), b.clone(), raw.clone()];
+/// let unique: IdCollector = all.into_iter().collect();
+/// assert_eq!(unique.into_vec(), [b, a, raw]);
+/// ```
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug, Default)]
+pub struct IdCollector {
+    allocated: IdSet,
+    raw: alloc::collections::BTreeSet<u64>,
+    ids: alloc::vec::Vec<Id>,
+}
+
+#[cfg(feature = "alloc")]
+impl IdCollector {
+    /// Create an empty collector.
     #[inline]
-    fn from(mut id: Id) -> Self {
-        id.ensure_init().get()
+    pub fn new() -> Self {
+        Self {
+            allocated: IdSet::new(),
+            raw: alloc::collections::BTreeSet::new(),
+            ids: alloc::vec::Vec::new(),
+        }
     }
-}
 
-impl From<&Id> for u64 {
+    /// Add `id`, lazily initializing it if needed. Returns true if it wasn't
+    /// already present.
+    pub fn insert(&mut self, id: Id) -> bool {
+        let added = if id.seq() <= self.allocated.max_seq {
+            self.allocated.insert(&id)
+        } else {
+            self.raw.insert(id.get())
+        };
+        if added {
+            self.ids.push(id);
+        }
+        added
+    }
+
+    /// Returns the unique ids collected so far, in first-seen order.
     #[inline]
-    fn from(id: &Id) -> Self {
-        id.get()
+    pub fn as_slice(&self) -> &[Id] {
+        &self.ids
     }
-}
 
-impl From<Id> for NonZeroU64 {
+    /// Returns the number of unique ids collected.
     #[inline]
-    fn from(mut id: Id) -> Self {
-        id.ensure_init()
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Returns true if no ids have been collected.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Returns the unique ids, in first-seen order.
+    #[inline]
+    pub fn into_vec(self) -> alloc::vec::Vec<Id> {
+        self.ids
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Extend<Id> for IdCollector {
+    fn extend<I: IntoIterator<Item = Id>>(&mut self, iter: I) {
+        for id in iter {
+            self.insert(id);
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl core::iter::FromIterator<Id> for IdCollector {
+    fn from_iter<I: IntoIterator<Item = Id>>(iter: I) -> Self {
+        let mut c = Self::new();
+        c.extend(iter);
+        c
+    }
+}
+
+/// The error type for fallible operations in this crate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum IdError {
+    /// The global id counter would overflow.
+    Overflow,
+    /// An id value was zero, which is reserved to mean "uninitialized".
+    Zero,
+    /// Input (for example, a byte slice) had the wrong length.
+    BadLength,
+    /// Input could not be parsed as an id.
+    Parse,
+    /// An id's value doesn't look like one the allocator has handed out.
+    Unallocated,
+    /// A seq given alongside an id's value didn't match it.
+    SeqMismatch,
+}
+
+impl core::fmt::Display for IdError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            IdError::Overflow => "id counter overflow",
+            IdError::Zero => "id value must be nonzero",
+            IdError::BadLength => "wrong input length for an id",
+            IdError::Parse => "invalid id syntax",
+            IdError::Unallocated => "id value was never allocated",
+            IdError::SeqMismatch => "id seq doesn't match its value",
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for IdError {}
+
+static ID_ALLOC: AtomicU64 = AtomicU64::new(Id::START_SEQ);
+
+// Parses `LAZY_ID_START_SEQ` for `Id::START_SEQ`. As in `__id_from_const`, we
+// can't panic in const on our MSRV, so out-of-bounds indexing is how bad values
+// become compile errors (as does overflow).
+#[allow(clippy::no_effect, clippy::unnecessary_operation)]
+const fn parse_start_seq(s: Option<&str>) -> u64 {
+    let bytes = match s {
+        Some(s) => s.as_bytes(),
+        None => return 1,
+    };
+    let mut v = 0u64;
+    let mut i = 0;
+    while i < bytes.len() {
+        let digit = bytes[i];
+        // Not a decimal digit.
+        [()][(digit < b'0' || digit > b'9') as usize];
+        v = v * 10 + (digit - b'0') as u64;
+        i += 1;
+    }
+    // Empty, zero, or past `i64::MAX`.
+    [()][(v == 0 || v > i64::MAX as u64) as usize];
+    v
+}
+
+// The next block of seqs to be claimed by an `IdAllocator`.
+static BLOCK_ALLOC: AtomicU64 = AtomicU64::new(0);
+
+// Top 16 bits are the epoch, low 48 are the local counter, which starts at 1.
+static EPOCH_ALLOC: AtomicU64 = AtomicU64::new(1);
+
+// Returns `None` if the local part has overflowed. Like `next_seq`, we check
+// against the top bit of the local part (and not for the carry into the epoch),
+// so that threads suspended between the increment and the check can't cause
+// duplicates.
+#[inline]
+fn epoch_state_to_id(state: u64) -> Option<NonZeroU64> {
+    let local = state & EpochId::LOCAL_MASK;
+    if local >> (EpochId::LOCAL_BITS - 1) != 0 {
+        return None;
+    }
+    debug_assert!(local != 0);
+    NonZeroU64::new(state)
+}
+
+// Child seq to parent seq, for `Id::new_after`. `Mutex::new` isn't const on our
+// MSRV, so this is a leaked `Box` that's created on first use.
+#[cfg(feature = "std")]
+static LINEAGE: AtomicPtr<std::sync::Mutex<std::collections::HashMap<u64, u64>>> =
+    AtomicPtr::new(core::ptr::null_mut());
+
+#[cfg(feature = "std")]
+fn lineage() -> &'static std::sync::Mutex<std::collections::HashMap<u64, u64>> {
+    use core::sync::atomic::Ordering::{AcqRel, Acquire};
+    let mut ptr = LINEAGE.load(Acquire);
+    if ptr.is_null() {
+        let new = std::boxed::Box::into_raw(std::boxed::Box::default());
+        ptr = match LINEAGE.compare_exchange(ptr, new, AcqRel, Acquire) {
+            Ok(_) => new,
+            Err(winner) => {
+                // SAFETY: We just created `new`, and nobody else has seen it.
+                drop(unsafe { std::boxed::Box::from_raw(new) });
+                winner
+            }
+        };
+    }
+    // SAFETY: Once non-null, `LINEAGE` always points to a leaked `Box`.
+    unsafe { &*ptr }
+}
+
+#[cfg(feature = "std")]
+static ON_ALLOC: AtomicPtr<()> = AtomicPtr::new(core::ptr::null_mut());
+
+#[inline]
+fn on_alloc(id: NonZeroU64) {
+    #[cfg(feature = "std")]
+    {
+        let f = ON_ALLOC.load(Relaxed);
+        if !f.is_null() {
+            // SAFETY: The only non-null values ever stored in `ON_ALLOC` come
+            // from a `fn(NonZeroU64)` in `Id::set_on_alloc`.
+            let f: fn(NonZeroU64) = unsafe { core::mem::transmute(f) };
+            f(id);
+        }
     }
+    #[cfg(not(feature = "std"))]
+    let _ = id;
 }
 
-static ID_ALLOC: AtomicU64 = AtomicU64::new(1);
+// The ordering used for the global counter. `Relaxed` is all we need (see
+// `next_seq`), and is what's used by default. The `seqcst-counter` feature only
+// exists so that the cost of something stronger can be measured on weakly
+// ordered targets (see `benches/contention.rs`).
+const COUNTER_ORDER: Ordering = if cfg!(feature = "seqcst-counter") {
+    Ordering::SeqCst
+} else {
+    Relaxed
+};
 
+// All increments of the global counter go through here.
 #[inline]
+fn bump_counter(counter: &AtomicU64) -> u64 {
+    counter.fetch_add(1, COUNTER_ORDER)
+}
+
+// Returns `None` if `seq` is past the point where we must abort. Testing
+// `seq > i64::MAX` (and not `seq == 0`) avoids the case where a thread
+// allocates the seq that causes the wrap, and is suspended before the check.
+// During the period when it's suspended, some number of ids may be allocated,
+// which would be duplicates of existing ids.
+#[inline]
+fn checked_seq(seq: u64) -> Option<NonZeroU64> {
+    if seq > (i64::MAX as u64) {
+        return None;
+    }
+    NonZeroU64::new(seq)
+}
+
+// Reserves `n` consecutive seqs from `counter`, returning the first, or `None`
+// (leaving the counter untouched) if the last one would be past `i64::MAX`.
+// This is a CAS loop rather than a `fetch_add`, since a `fetch_add` that
+// overshoots can't be undone.
+#[inline]
+fn reserve_seqs(counter: &AtomicU64, n: u64) -> Option<u64> {
+    let mut cur = counter.load(Relaxed);
+    loop {
+        // `cur` can briefly be past the limit (see `checked_seq`), in which
+        // case `checked_sub` fails.
+        let left = (i64::MAX as u64 + 1).checked_sub(cur)?;
+        if n > left {
+            return None;
+        }
+        match counter.compare_exchange_weak(cur, cur + n, COUNTER_ORDER, Relaxed) {
+            Ok(_) => return Some(cur),
+            Err(e) => cur = e,
+        }
+    }
+}
+
+#[inline]
+#[track_caller]
 fn next_seq() -> NonZeroU64 {
     // Relaxed is fine here, because we only care that this be distinct from
     // other ids — ensured by it being an atomic increment with an overflow
     // check. It's fine and expected that IDs might be skipped. Note that this
     // doesn't need to synchronize in any way with the atomic ops in `sync::Id`.
-    let seq = ID_ALLOC.fetch_add(1, Relaxed);
-    if seq > (i64::max_value() as u64) {
+    match checked_seq(bump_counter(&ID_ALLOC)) {
+        Some(seq) => seq,
         // Protect against overflow (which would take decades) by aborting
-        // (bringing down just our thread by panicing isn't sufficient).
-        // Testing the `seq > i64::MAX` (and not `seq == 0`) avoids the case
-        // where a thread allocate the seq that causes the wrap, and is
-        // suspended before the check. During the period when it's
-        // suspended, some number of ids may be allocated, which would
-        // be duplicates of existing ids.
-        nostd_abort();
+        // (bringing down just our thread by panicing isn't sufficient). We
+        // start at 1, so this is also the only way to get 0.
+        None => nostd_abort(core::panic::Location::caller()),
+    }
+}
+
+// `loc` is the location of whoever asked for the id that overflowed (as far
+// as `#[track_caller]` reaches), since a location inside this crate wouldn't be
+// very helpful.
+#[cold]
+#[inline(never)]
+fn nostd_abort(loc: &'static core::panic::Location<'static>) -> ! {
+    struct PanicOnDrop(&'static core::panic::Location<'static>);
+    impl Drop for PanicOnDrop {
+        #[inline]
+        fn drop(&mut self) {
+            panic!(
+                "Id counter overflow (id requested at {}). Aborting by double panic (2/2)",
+                self.0
+            );
+        }
     }
-    debug_assert!(seq != 0);
-    // Safety: we start at 1, and protect against overflow, so `seq` can't be 0.
-    unsafe { NonZeroU64::new_unchecked(seq) }
+    let _p = PanicOnDrop(loc);
+    panic!(
+        "Id counter overflow (id requested at {}). Aborting by double panic (1/2)",
+        loc
+    );
 }
 
 #[cfg(test)]
 mod test {
+    #[test]
+    fn counter_overflow() {
+        use super::{bump_counter, checked_seq};
+        use core::sync::atomic::AtomicU64;
+        let max = i64::MAX as u64;
+        let counter = AtomicU64::new(max - 1);
+        let a = checked_seq(bump_counter(&counter)).unwrap();
+        let b = checked_seq(bump_counter(&counter)).unwrap();
+        assert_eq!((a.get(), b.get()), (max - 1, max));
+        for _ in 0..3 {
+            assert_eq!(checked_seq(bump_counter(&counter)), None);
+        }
+        counter.store(u64::MAX, core::sync::atomic::Ordering::Relaxed);
+        assert_eq!(checked_seq(bump_counter(&counter)), None);
+        assert_eq!(checked_seq(bump_counter(&counter)), None);
+        assert_eq!(checked_seq(1).map(|s| s.get()), Some(1));
+    }
+
+    #[test]
+    fn reserve_overflow() {
+        use super::{bump_counter, checked_seq, reserve_seqs};
+        use core::sync::atomic::{AtomicU64, Ordering::Relaxed};
+        let max = i64::MAX as u64;
+        let counter = AtomicU64::new(max - 9);
+        // Doesn't fit, and doesn't consume anything.
+        assert_eq!(reserve_seqs(&counter, 11), None);
+        assert_eq!(reserve_seqs(&counter, u64::MAX), None);
+        assert_eq!(counter.load(Relaxed), max - 9);
+        // Exactly fits.
+        assert_eq!(reserve_seqs(&counter, 10), Some(max - 9));
+        assert_eq!(counter.load(Relaxed), max + 1);
+        assert_eq!(reserve_seqs(&counter, 0), Some(max + 1));
+        assert_eq!(reserve_seqs(&counter, 1), None);
+        assert_eq!(checked_seq(bump_counter(&counter)), None);
+        // Past the limit (as happens when `next_seq` is about to abort).
+        assert_eq!(reserve_seqs(&counter, 0), None);
+        assert_eq!(reserve_seqs(&AtomicU64::new(1), max), Some(1));
+        assert_eq!(reserve_seqs(&AtomicU64::new(1), max + 1), None);
+    }
+
+    #[test]
+    fn start_seq() {
+        use super::parse_start_seq;
+        assert_eq!(parse_start_seq(None), 1);
+        assert_eq!(parse_start_seq(Some("1")), 1);
+        assert_eq!(parse_start_seq(Some("1000000")), 1_000_000);
+        assert_eq!(
+            parse_start_seq(Some("9223372036854775807")),
+            i64::MAX as u64
+        );
+        assert_eq!(
+            super::Id::MAX_COUNT,
+            (i64::MAX as u64 - super::Id::START_SEQ + 2) / 2
+        );
+    }
+
+    // A counter set up like `ID_ALLOC` hands out the base first, and stops
+    // at the same bound as ever. Whether the real counter starts at the
+    // base is checked in `tests/sequential.rs`, where nothing else allocates.
+    #[test]
+    fn start_seq_counter_bounds() {
+        use super::{bump_counter, checked_seq, Id};
+        use core::sync::atomic::{AtomicU64, Ordering::Relaxed};
+        let max = i64::MAX as u64;
+        let counter = AtomicU64::new(Id::START_SEQ);
+        assert_eq!(
+            checked_seq(bump_counter(&counter)).unwrap().get(),
+            Id::START_SEQ
+        );
+        counter.store(max - 1, Relaxed);
+        assert_eq!(checked_seq(bump_counter(&counter)).unwrap().get(), max - 1);
+        assert_eq!(checked_seq(bump_counter(&counter)).unwrap().get(), max);
+        // This is what `next_seq` aborts on.
+        assert_eq!(checked_seq(bump_counter(&counter)), None);
+    }
+
+    #[test]
+    fn epoch_local_overflow() {
+        use super::{epoch_state_to_id, EpochId};
+        let last = (1 << 47) - 1;
+        for epoch in [0u64, 1, 0xffff].iter().cloned() {
+            let base = epoch << EpochId::LOCAL_BITS;
+            let id = EpochId(epoch_state_to_id(base | last).unwrap());
+            assert_eq!((id.epoch() as u64, id.local()), (epoch, last));
+            assert_eq!(epoch_state_to_id(base | (last + 1)), None);
+            assert_eq!(epoch_state_to_id(base | EpochId::LOCAL_MASK), None);
+            let first = EpochId(epoch_state_to_id(base | 1).unwrap());
+            assert_eq!((first.epoch() as u64, first.local()), (epoch, 1));
+        }
+    }
+
     #[test]
     fn mixing() {
         // no longer have `unsync`...
@@ -515,6 +3977,7 @@ mod test {
         fn syncunmix(u: u64) -> u64 {
             u.wrapping_mul(super::Id::ID2SEQ)
         }
+        assert!(super::Id::verify_constants());
         let count = if cfg!(miri) { 100 } else { 10000 };
         // true for all integers, holds because they're odd and becuase of
         // the properties of
@@ -528,17 +3991,3 @@ mod test {
         }
     }
 }
-
-#[cold]
-#[inline(never)]
-fn nostd_abort() -> ! {
-    struct PanicOnDrop();
-    impl Drop for PanicOnDrop {
-        #[inline]
-        fn drop(&mut self) {
-            panic!("Id counter overflow. Aborting by double panic (2/2)");
-        }
-    }
-    let _p = PanicOnDrop();
-    panic!("Id counter overflow. Aborting by double panic (1/2)");
-}
@@ -0,0 +1,35 @@
+// This is its own test binary, since the hook is process-wide, and would
+// otherwise be called for every id the other tests allocate.
+#![cfg(feature = "std")]
+use core::num::NonZeroU64;
+use lazy_id::Id;
+use std::cell::RefCell;
+
+std::thread_local! {
+    static SEEN: RefCell<Vec<u64>> = RefCell::new(Vec::new());
+}
+
+fn record(id: NonZeroU64) {
+    SEEN.with(|s| s.borrow_mut().push(id.get()));
+}
+
+fn seen() -> Vec<u64> {
+    SEEN.with(|s| s.borrow().clone())
+}
+
+#[test]
+fn test_on_alloc() {
+    Id::set_on_alloc(record);
+    let eager = Id::new();
+    let lazy = Id::lazy();
+    let raw = Id::from_raw_integer(NonZeroU64::new(400).unwrap());
+    assert_eq!(seen(), [eager.get()]);
+    let lazy_v = lazy.get();
+    let _ = (lazy.clone(), raw.get());
+    assert_eq!(seen(), [eager.get(), lazy_v]);
+
+    Id::clear_on_alloc();
+    let _ = Id::new();
+    let _ = Id::lazy().get();
+    assert_eq!(seen(), [eager.get(), lazy_v]);
+}
@@ -0,0 +1,32 @@
+// This is its own test binary, so that no other tests are allocating ids
+// concurrently.
+#![cfg(feature = "test-util")]
+use core::num::NonZeroU64;
+use lazy_id::{Id, IdMixer};
+
+#[test]
+fn test_sequential() {
+    let ids: Vec<Id> = (0..10).map(|_| Id::new_sequential()).collect();
+    // nothing else in this binary allocates, so we start at the base.
+    assert_eq!(ids[0].get(), Id::START_SEQ);
+    for w in ids.windows(2) {
+        assert_eq!(w[0].get() + 1, w[1].get());
+    }
+    // interleaving with normal allocation still consumes seqs.
+    let last = ids[9].get();
+    let _ = Id::new();
+    assert_eq!(Id::new_sequential().get(), last + 2);
+    // lazy defaults don't consume seqs until they're used.
+    let lazy = lazy_id::LazyDefault::default();
+    assert_eq!(Id::new_sequential().get(), last + 3);
+    let _ = lazy.get();
+    assert_eq!(Id::new_sequential().get(), last + 5);
+    // the exact boundary of `likely_raw_constructed`.
+    let next = Id::peek_next_seq();
+    let mixed = |seq| Id::from_raw_integer(IdMixer::DEFAULT.mix(NonZeroU64::new(seq).unwrap()));
+    assert!(!mixed(next - 1).likely_raw_constructed());
+    assert!(mixed(next).likely_raw_constructed());
+    let allocated = Id::new();
+    assert_eq!(allocated.seq(), next);
+    assert!(!mixed(next).likely_raw_constructed());
+}
@@ -1,5 +1,10 @@
 use lazy_id::Id;
 
+// An id with the given seq, as if it had been allocated.
+fn id_with_seq(seq: u64) -> Id {
+    Id::from_raw_integer(lazy_id::IdMixer::DEFAULT.mix(core::num::NonZeroU64::new(seq).unwrap()))
+}
+
 #[test]
 fn test_eq() {
     assert_ne!(Id::new(), Id::new());
@@ -74,6 +79,12 @@ fn test_convert() {
     assert_eq!(vnz, core::num::NonZeroU64::from(id));
     // silly, tbh
     assert_ne!(u64::from(Id::lazy()), u64::from(Id::lazy()));
+    let mut lazy = Id::lazy();
+    let lv = u64::from(&mut lazy);
+    assert_eq!(lv, lazy.get());
+    assert_eq!(u64::from(&mut lazy), lv);
+    let mut eager = Id::new();
+    assert_eq!(u64::from(&mut eager), eager.get());
 }
 
 #[test]
@@ -84,3 +95,1458 @@ fn test_etc() {
     let i = Id::default();
     assert_eq!(i, i);
 }
+
+#[test]
+fn test_parts() {
+    let v = Id::from_parts(0xdead_beef, 0x1234_5678).unwrap();
+    assert_eq!(v.get(), 0xdead_beef_1234_5678);
+    assert_eq!(v.high(), 0xdead_beef);
+    assert_eq!(v.low(), 0x1234_5678);
+    assert_eq!(Id::from_parts(0, 1).unwrap().get(), 1);
+    assert_eq!(Id::from_parts(1, 0).unwrap().get(), 1 << 32);
+    assert!(Id::from_parts(0, 0).is_none());
+    let fresh = Id::lazy();
+    let round = Id::from_parts(fresh.high(), fresh.low()).unwrap();
+    assert_eq!(round, fresh);
+}
+
+#[test]
+fn test_seeded() {
+    use lazy_id::SeededIds;
+    let a: Vec<Id> = SeededIds::new(7).take(50).collect();
+    let b: Vec<Id> = SeededIds::new(7).take(50).collect();
+    assert_eq!(a, b);
+    let c: Vec<Id> = SeededIds::new(8).take(50).collect();
+    assert_ne!(a, c);
+    let mut seen = std::collections::HashSet::new();
+    assert!(a.iter().all(|id| seen.insert(id.get())));
+    // The LCG steps to zero from this state, which must be skipped.
+    let mut z = SeededIds::new(0x9995_b5b6_2153_5015);
+    assert_eq!(z.next().get(), 1442695040888963407);
+}
+
+#[test]
+fn test_eq_value() {
+    let a = Id::lazy();
+    let b = Id::new();
+    let v = a.get();
+    for other in &[v, b.get(), 0, !v] {
+        assert_eq!(a.eq_value(*other), a == *other);
+        assert_eq!(b.eq_value(*other), b == *other);
+    }
+    assert!(a.eq_nonzero(a.get_nonzero()));
+    assert!(!a.eq_nonzero(b.get_nonzero()));
+    // forces init
+    let c = Id::lazy();
+    assert!(!c.eq_value(0));
+    assert!(c.eq_value(c.get()));
+}
+
+#[test]
+fn test_unique_on_clone() {
+    use lazy_id::UniqueOnClone;
+    let x = UniqueOnClone(Id::lazy());
+    assert_ne!(x.clone().0, x.0);
+    let y = UniqueOnClone::from(Id::new());
+    let z = y.clone();
+    assert_ne!(z.0, y.0);
+    assert_ne!(z.clone().0, z.0);
+    // the inner id's clone still duplicates
+    assert_eq!(y.0.clone(), y.0);
+}
+
+#[test]
+fn test_mixer() {
+    use core::num::NonZeroU64;
+    use lazy_id::{IdMixer, SeededIds};
+    assert_eq!(IdMixer::default(), IdMixer::DEFAULT);
+    let d = IdMixer::DEFAULT;
+    assert_eq!(IdMixer::new(d.multiplier()), Some(d));
+    assert_eq!(d.multiplier().wrapping_mul(d.inverse()), 1);
+    for even in &[0, 2, 4, 1 << 63, u64::MAX - 1] {
+        assert!(IdMixer::new(*even).is_none());
+    }
+    let count = if cfg!(miri) { 20 } else { 1000 };
+    // Invertibility has to hold for arbitrary odd multipliers, not just ours.
+    let multipliers = SeededIds::new(99).take(count).map(|id| id.get() | 1);
+    for (i, mul) in multipliers.chain(vec![1, 3, u64::MAX]).enumerate() {
+        let m = IdMixer::new(mul).unwrap();
+        assert_eq!(m.multiplier().wrapping_mul(m.inverse()), 1, "{}", mul);
+        let i = i as u64;
+        for v in &[i + 1, !i, 1 << 63, u64::MAX] {
+            let nz = NonZeroU64::new(*v).unwrap();
+            assert_eq!(m.unmix(m.mix(nz).get()), *v, "{} {}", mul, v);
+        }
+    }
+    let m = IdMixer::new(0x9e37_79b9_7f4a_7c15).unwrap();
+    let a = Id::new_mixed(&m);
+    let b = Id::new_mixed(&m);
+    assert_ne!(a, b);
+    assert!(m.unmix(a.get()) < m.unmix(b.get()));
+}
+
+#[test]
+fn test_try_deref() {
+    let a = Id::lazy();
+    assert_eq!(a.try_deref(), None);
+    // still uninitialized after checking.
+    assert_eq!(a.try_deref(), None);
+    assert_eq!(*a, a.get());
+    assert_eq!(a.try_deref(), Some(&a.get()));
+    let b = Id::new();
+    assert_eq!(b.try_deref(), Some(&*b));
+}
+
+#[test]
+fn test_as_u64_ref() {
+    let a = Id::lazy();
+    let r = a.as_u64_ref();
+    let v = a.get();
+    assert_eq!(*r, v);
+    // other ids being allocated shouldn't disturb it.
+    let _others: Vec<Id> = (0..10).map(|_| Id::new()).collect();
+    let _ = a.clone();
+    assert_eq!(*r, v);
+    assert_eq!(r, a.as_u64_ref());
+    assert!(core::ptr::eq(r, &*a));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_lazy_arc() {
+    let id = Id::lazy_arc();
+    let threads: Vec<_> = (0..8)
+        .map(|_| {
+            let id = id.clone();
+            std::thread::spawn(move || id.get())
+        })
+        .collect();
+    let vals: Vec<u64> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+    assert!(vals.iter().all(|v| *v == id.get()));
+    let b = Id::lazy_box();
+    assert_ne!(*b, *id);
+}
+
+#[test]
+fn test_seq() {
+    let a = Id::lazy();
+    let b = Id::lazy();
+    // initialize in the opposite order they were created in.
+    let (bv, av) = (b.seq(), a.seq());
+    assert!(bv < av);
+    assert_eq!(a.seq(), a.get().wrapping_mul(0x1337_fe4415));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_sorted_by_seq() {
+    let ids: Vec<Id> = (0..50).map(|_| Id::new()).collect();
+    let mut shuffled = ids.clone();
+    // numeric order is unrelated to seq order.
+    shuffled.sort();
+    shuffled.reverse();
+    assert_eq!(lazy_id::sorted_by_seq(&shuffled), ids);
+    assert!(lazy_id::sorted_by_seq(&[]).is_empty());
+}
+
+#[test]
+fn test_explicit_ordering() {
+    use core::sync::atomic::Ordering::*;
+    for order in &[Relaxed, Acquire, SeqCst] {
+        let a = Id::lazy();
+        let v = a.get_with(*order);
+        assert_eq!(v, a.get());
+        assert_eq!(a.get_with(*order), v);
+    }
+    for (s, f) in &[
+        (Relaxed, Relaxed),
+        (Release, Relaxed),
+        (AcqRel, Acquire),
+        (SeqCst, SeqCst),
+        // `failure` stronger than `success` (which `compare_exchange` rejects
+        // before 1.64, but `success` gets upgraded).
+        (Relaxed, Acquire),
+        (Release, Acquire),
+        (Relaxed, SeqCst),
+    ] {
+        let a = Id::lazy();
+        let v = a.init_with(*s, *f);
+        assert_eq!(v, a.get_nonzero());
+        assert_eq!(a.init_with(*s, *f), v);
+    }
+}
+
+#[test]
+#[should_panic]
+fn test_get_with_release() {
+    let _ = Id::new().get_with(core::sync::atomic::Ordering::Release);
+}
+
+#[test]
+#[should_panic]
+fn test_init_with_bad_failure() {
+    use core::sync::atomic::Ordering::*;
+    let _ = Id::lazy().init_with(SeqCst, AcqRel);
+}
+
+#[test]
+fn test_cmp_nonzero() {
+    use core::num::NonZeroU64;
+    let a = Id::lazy();
+    let nz = a.get_nonzero();
+    assert_eq!(a, nz);
+    assert_eq!(nz, a);
+    assert!(a <= nz);
+    assert!(nz >= a);
+    let one = NonZeroU64::new(1).unwrap();
+    let max = NonZeroU64::new(u64::MAX).unwrap();
+    let b = Id::lazy();
+    assert!(b >= one);
+    assert!(one <= b);
+    assert!(b <= max);
+    assert!(max >= b);
+    assert_eq!(b.partial_cmp(&one), b.get().partial_cmp(&1));
+    assert_eq!(one.partial_cmp(&b), 1.partial_cmp(&b.get()));
+}
+
+#[test]
+fn test_ids_macro() {
+    let empty: [Id; 0] = lazy_id::ids![];
+    static TABLE: [Id; 4] = lazy_id::ids![1, 2, 3, u64::MAX,];
+    let vals: Vec<u64> = TABLE.iter().map(Id::get).collect();
+    assert_eq!(vals, [1, 2, 3, u64::MAX]);
+    assert!(empty.is_empty());
+    let local = lazy_id::ids![1 << 40];
+    assert_eq!(local[0].get(), 1 << 40);
+    assert!(Id::try_from_raw(0).is_none());
+    assert_eq!(Id::try_from_raw(7).unwrap(), 7);
+}
+
+#[test]
+fn test_fmt_flags() {
+    let small = Id::from_raw_integer(core::num::NonZeroU64::new(42).unwrap());
+    assert_eq!(format!("{:08}", small), "00000042");
+    assert_eq!(format!("{:>6}|", small), "    42|");
+    assert_eq!(format!("{:<6}|", small), "42    |");
+    assert_eq!(format!("{:^6}|", small), "  42  |");
+    assert_eq!(format!("{:*>5}", small), "***42");
+    assert_eq!(format!("{:+}", small), "+42");
+    let big = Id::new();
+    let v = big.get();
+    assert_eq!(format!("{:>25}", big), format!("{:>25}", v));
+    assert_eq!(format!("{:025}", big), format!("{:025}", v));
+    // `Debug` is a fixed format.
+    assert_eq!(format!("{:>60?}", big), format!("{:?}", big));
+    assert_eq!(format!("{:08?}", small), format!("{:?}", small));
+}
+
+#[test]
+fn test_epoch_id() {
+    use lazy_id::EpochId;
+    let a = EpochId::new();
+    let b = EpochId::new();
+    assert_ne!(a, b);
+    assert_ne!(a.local(), 0);
+    // other tests may bump concurrently, so only check what must hold.
+    assert!(a.epoch() < b.epoch() || a.local() < b.local());
+    assert!(a < b);
+    let bumped = Id::bump_epoch();
+    let c = EpochId::new();
+    assert!(c.epoch() >= bumped && bumped > b.epoch());
+    assert_eq!(c.get() >> 48, u64::from(c.epoch()));
+    assert_eq!(c.get() & ((1 << 48) - 1), c.local());
+    assert_eq!(u64::from(c), c.get());
+    assert_eq!(
+        format!("{:?}", c),
+        format!("EpochId(epoch={}; local={})", c.epoch(), c.local())
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde_json() {
+    let id = Id::new();
+    let json = serde_json::to_string(&id).unwrap();
+    assert_eq!(json, format!("\"{}\"", id.get()));
+    assert_eq!(serde_json::from_str::<Id>(&json).unwrap(), id);
+    // above 2^53, which JSON numbers can't necessarily represent.
+    let big = Id::try_from_raw(u64::MAX - 1).unwrap();
+    let json = serde_json::to_string(&big).unwrap();
+    assert_eq!(json, "\"18446744073709551614\"");
+    assert_eq!(serde_json::from_str::<Id>(&json).unwrap(), big);
+    // integers are accepted too.
+    assert_eq!(serde_json::from_str::<Id>("400").unwrap(), 400);
+    for bad in &["0", "\"0\"", "-1", "\"abc\"", "\"-5\"", "1.5", "null"] {
+        assert!(serde_json::from_str::<Id>(bad).is_err(), "{}", bad);
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde_bincode() {
+    let id = Id::lazy();
+    let bytes = bincode::serialize(&id).unwrap();
+    assert_eq!(bytes, bincode::serialize(&id.get()).unwrap());
+    assert_eq!(bincode::deserialize::<Id>(&bytes).unwrap(), id);
+    let zero = bincode::serialize(&0u64).unwrap();
+    assert!(bincode::deserialize::<Id>(&zero).is_err());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_wait_init() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    let id = Id::lazy_arc();
+    let data = Arc::new(AtomicUsize::new(0));
+    let waiters: Vec<_> = (0..4)
+        .map(|_| {
+            let (id, data) = (id.clone(), data.clone());
+            std::thread::spawn(move || (id.wait_init(), data.load(Ordering::Relaxed)))
+        })
+        .collect();
+    data.store(123, Ordering::Relaxed);
+    let v = id.init_with(Ordering::Release, Ordering::Relaxed);
+    for w in waiters {
+        assert_eq!(w.join().unwrap(), (v, 123));
+    }
+    // already initialized.
+    let e = Id::new();
+    assert_eq!(e.wait_init(), e.get_nonzero());
+}
+
+#[test]
+fn test_peek_next_seq() {
+    // other tests are allocating concurrently, so this is all we can check.
+    let before = Id::peek_next_seq();
+    let id = Id::new();
+    assert!(id.seq() >= before);
+    assert!(Id::peek_next_seq() > id.seq());
+}
+
+#[test]
+fn test_frozen_consistency() {
+    use lazy_id::FrozenId;
+    use std::collections::hash_map::DefaultHasher;
+    use std::collections::HashMap;
+    use std::hash::{Hash, Hasher};
+    fn hash_of<T: Hash + ?Sized>(v: &T) -> u64 {
+        let mut h = DefaultHasher::new();
+        v.hash(&mut h);
+        h.finish()
+    }
+    let ids: Vec<Id> = (0..20).map(|_| Id::lazy()).collect();
+    for id in &ids {
+        let f: FrozenId = id.freeze();
+        assert_eq!(*id, f);
+        assert_eq!(f, *id);
+        assert_eq!(f, id.get());
+        assert_eq!(id.get(), f);
+        assert_eq!(hash_of(id), hash_of(&f));
+        assert_eq!(hash_of(&f), hash_of(&id.get()));
+        assert_eq!(f.thaw(), *id);
+        assert_eq!(FrozenId::from(id), f);
+        assert_eq!(u64::from(f), id.get());
+        assert_eq!(f.to_string(), id.to_string());
+        assert_eq!(format!("{:?}", f), format!("Frozen{:?}", id));
+    }
+    // Both `Borrow<u64>`, so each can look up the other via the value.
+    #[allow(clippy::mutable_key_type)]
+    let by_id: HashMap<Id, usize> = ids.iter().cloned().zip(0..).collect();
+    let by_frozen: HashMap<FrozenId, usize> = ids.iter().map(Id::freeze).zip(0..).collect();
+    for (i, id) in ids.iter().enumerate() {
+        assert_eq!(by_id[&id.freeze().get()], i);
+        assert_eq!(by_frozen[&id.get()], i);
+    }
+    assert_ne!(ids[0].freeze(), ids[1]);
+}
+
+#[test]
+fn test_error_display() {
+    use lazy_id::IdError;
+    assert_eq!(IdError::Overflow.to_string(), "id counter overflow");
+    assert_eq!(IdError::Zero.to_string(), "id value must be nonzero");
+    assert_eq!(
+        IdError::BadLength.to_string(),
+        "wrong input length for an id"
+    );
+    assert_eq!(IdError::Parse.to_string(), "invalid id syntax");
+    assert_eq!(
+        IdError::Unallocated.to_string(),
+        "id value was never allocated"
+    );
+    assert_eq!(
+        IdError::SeqMismatch.to_string(),
+        "id seq doesn't match its value"
+    );
+    #[cfg(feature = "std")]
+    {
+        let e: Box<dyn std::error::Error> = Box::new(IdError::Zero);
+        assert_eq!(e.to_string(), "id value must be nonzero");
+    }
+}
+
+#[test]
+fn test_derive() {
+    let parent = Id::lazy();
+    let same = Id::from_raw_integer(parent.get_nonzero());
+    let children: Vec<Id> = (0..100).map(|i| parent.derive(i)).collect();
+    for (i, c) in children.iter().enumerate() {
+        assert_eq!(*c, same.derive(i as u64));
+        assert_ne!(*c, 0);
+    }
+    let mut vals: Vec<u64> = children.iter().map(Id::get).collect();
+    vals.sort_unstable();
+    vals.dedup();
+    assert_eq!(vals.len(), children.len());
+    assert_ne!(parent.derive(parent.seq()), 0);
+    assert_ne!(Id::new().derive(0), parent.derive(0));
+
+    // The index whose mix is zero gets a value that's never allocated.
+    const K: u64 = 0x9e37_79b9_7f4a_7c15;
+    let zero_index = parent.get().rotate_left(32) ^ K;
+    let special = parent.derive(zero_index);
+    assert_eq!(special, parent.derive(zero_index));
+    assert_eq!(special.seq(), u64::MAX);
+    assert!(Id::value_is_reserved(special.get()));
+}
+
+#[cfg(feature = "nightly")]
+#[test]
+fn test_step() {
+    let raw = |v: u64| Id::try_from_raw(v).unwrap();
+    let vals: Vec<u64> = (raw(1)..raw(5)).map(|id| id.get()).collect();
+    assert_eq!(vals, [1, 2, 3, 4]);
+    assert_eq!((raw(3)..=raw(3)).count(), 1);
+    assert_eq!((raw(10)..raw(3)).count(), 0);
+    let top: Vec<u64> = (raw(u64::MAX - 1)..=raw(u64::MAX))
+        .map(|id| id.get())
+        .collect();
+    assert_eq!(top, [u64::MAX - 1, u64::MAX]);
+    // never steps to zero.
+    let back: Vec<u64> = (raw(1)..raw(4)).rev().map(|id| id.get()).collect();
+    assert_eq!(back, [3, 2, 1]);
+    assert_eq!((raw(u64::MAX)..=raw(u64::MAX)).count(), 1);
+}
+
+#[test]
+fn test_value_is_reserved() {
+    let max = i64::MAX as u64;
+    // Zero, and the start of the global counter.
+    assert!(!Id::value_is_reserved(0));
+    assert!(!Id::value_is_reserved(id_with_seq(1).get()));
+    // Both sides of `i64::MAX`: the end of the global counter, and the start
+    // of the `IdAllocator` blocks, neither of which is reserved.
+    assert!(!Id::value_is_reserved(id_with_seq(max - 1).get()));
+    assert!(!Id::value_is_reserved(id_with_seq(max).get()));
+    assert!(!Id::value_is_reserved(id_with_seq(max + 1).get()));
+    assert!(!Id::value_is_reserved(id_with_seq(max + 2).get()));
+    // Both sides of the end of the `IdAllocator` blocks.
+    assert!(!Id::value_is_reserved(id_with_seq((3 << 62) - 1).get()));
+    assert!(Id::value_is_reserved(id_with_seq(3 << 62).get()));
+    assert!(Id::value_is_reserved(id_with_seq((3 << 62) + 1).get()));
+    assert!(Id::value_is_reserved(id_with_seq(u64::MAX).get()));
+    static ALLOC: lazy_id::IdAllocator = lazy_id::IdAllocator::new();
+    for _ in 0..100 {
+        assert!(!Id::value_is_reserved(Id::new().get()));
+        let id = Id::new_with_allocator(&ALLOC);
+        assert!(!Id::value_is_reserved(id.get()));
+        assert_eq!(id.seq() >> 62, 0b10);
+    }
+}
+
+#[test]
+fn test_atomic_id_fetch_max() {
+    use lazy_id::AtomicId;
+    use std::sync::Arc;
+    let cell = AtomicId::default();
+    assert_eq!(format!("{:?}", cell), "AtomicId(<empty>)");
+    let ids: Vec<Vec<Id>> = (0..8)
+        .map(|_| (0..200).map(|_| Id::new()).collect())
+        .collect();
+    let expected = ids.iter().flatten().max().unwrap().clone();
+    let cell = Arc::new(cell);
+    let threads: Vec<_> = ids
+        .into_iter()
+        .map(|chunk| {
+            let cell = cell.clone();
+            std::thread::spawn(move || {
+                for id in &chunk {
+                    if let Some(prev) = cell.fetch_max(id) {
+                        // the cell only ever grows.
+                        assert!(cell.load().unwrap() >= prev);
+                    }
+                }
+            })
+        })
+        .collect();
+    for t in threads {
+        t.join().unwrap();
+    }
+    assert_eq!(cell.load(), Some(expected.clone()));
+    // smaller values are rejected.
+    let small = Id::try_from_raw(1).unwrap();
+    assert_eq!(cell.fetch_max(&small), Some(expected.clone()));
+    assert_eq!(cell.load(), Some(expected.clone()));
+    let other = AtomicId::new(&small);
+    other.store(&expected);
+    assert_eq!(other.load(), Some(expected));
+}
+
+#[test]
+fn test_lazy_default() {
+    use lazy_id::LazyDefault;
+    #[derive(Default)]
+    struct Thing {
+        id: LazyDefault,
+    }
+    let a = Thing::default();
+    let b = LazyDefault::default();
+    // no value taken from the counter yet.
+    assert!(a.id.try_deref().is_none());
+    assert!(b.try_deref().is_none());
+    let av = a.id.get();
+    assert_eq!(a.id.try_deref(), Some(&av));
+    assert_ne!(*b, a.id.0);
+    assert!(LazyDefault::new().0.try_deref().is_none());
+}
+
+#[test]
+fn test_eq_refs() {
+    let ids: Vec<Id> = (0..10).map(|_| Id::lazy()).collect();
+    let vals: Vec<u64> = ids.iter().map(Id::get).collect();
+    let id = &ids[3];
+    let v = &vals[3];
+    assert!(*id == v);
+    assert!(v == *id);
+    assert!(id == *v);
+    assert!(*v == id);
+    assert!(*id == id);
+    assert!(id == *id);
+    assert_eq!(vals.iter().find(|&x| *id == x), Some(v));
+    assert_eq!(vals.iter().find(|&&x| id == x), Some(v));
+    assert_eq!(vals.iter().position(|x| x == *id), Some(3));
+    assert_eq!(ids.iter().find(|&x| x == *v).map(Id::get), Some(*v));
+    assert_eq!(ids.iter().filter(|x| **x == id).count(), 1);
+    assert_eq!(ids.iter().filter(|&x| *v == x).count(), 1);
+    assert!(ids[4] != v);
+}
+
+#[test]
+fn test_layout() {
+    use core::mem::{align_of, size_of};
+    use core::sync::atomic::AtomicU64;
+    assert_eq!(size_of::<Id>(), 8);
+    assert_eq!(size_of::<Id>(), size_of::<u64>());
+    assert_eq!(align_of::<Id>(), align_of::<AtomicU64>());
+    assert!(align_of::<Id>() >= align_of::<u64>());
+    // reading through a `*const u64`, as C code might.
+    let id = Id::new();
+    let p = &id as *const Id as *const u64;
+    assert_eq!(unsafe { p.read() }, id.get());
+    let lazy = Id::lazy();
+    assert_eq!(unsafe { (&lazy as *const Id as *const u64).read() }, 0);
+}
+
+#[test]
+fn test_observe() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Barrier};
+    let a = Id::lazy();
+    let (v, first) = a.observe();
+    assert!(first);
+    assert_eq!(v, a.get());
+    assert_eq!(a.observe(), (v, false));
+    let b = Id::lazy();
+    let bv = b.get();
+    assert_eq!(b.observe(), (bv, false));
+    // exactly one thread wins when racing.
+    for _ in 0..if cfg!(miri) { 2 } else { 20 } {
+        let shared = Arc::new(Id::lazy());
+        let wins = Arc::new(AtomicUsize::new(0));
+        let barrier = Arc::new(Barrier::new(4));
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let (shared, wins, barrier) = (shared.clone(), wins.clone(), barrier.clone());
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    let (v, won) = shared.observe();
+                    if won {
+                        wins.fetch_add(1, Ordering::Relaxed);
+                    }
+                    v
+                })
+            })
+            .collect();
+        for t in threads {
+            assert_eq!(t.join().unwrap(), shared.get());
+        }
+        assert_eq!(wins.load(Ordering::Relaxed), 1);
+    }
+}
+
+#[test]
+fn test_seq_string() {
+    use lazy_id::IdError;
+    let a = Id::lazy();
+    let s = a.seq().to_string();
+    #[cfg(feature = "alloc")]
+    assert_eq!(a.to_seq_string(), s);
+    let back = Id::from_seq_string(&s).unwrap();
+    assert_eq!(back, a);
+    assert_eq!(back.seq(), a.seq());
+    let raw = Id::try_from_raw(u64::MAX).unwrap();
+    assert_eq!(Id::from_seq_string(&raw.seq().to_string()), Ok(raw));
+    assert_eq!(Id::from_seq_string("0"), Err(IdError::Zero));
+    assert_eq!(Id::from_seq_string("-1"), Err(IdError::Parse));
+    assert_eq!(Id::from_seq_string(""), Err(IdError::Parse));
+    assert_eq!(
+        Id::from_seq_string("18446744073709551616"),
+        Err(IdError::Parse)
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde_seq() {
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Thing {
+        #[serde(with = "lazy_id::serde_seq")]
+        id: Id,
+    }
+    let t = Thing { id: Id::new() };
+    let json = serde_json::to_string(&t).unwrap();
+    assert_eq!(json, format!("{{\"id\":{}}}", t.id.seq()));
+    let back: Thing = serde_json::from_str(&json).unwrap();
+    assert_eq!(back.id, t.id);
+    let bytes = bincode::serialize(&t).unwrap();
+    assert_eq!(bytes, bincode::serialize(&t.id.seq()).unwrap());
+    let back: Thing = bincode::deserialize(&bytes).unwrap();
+    assert_eq!(back.id, t.id);
+    assert!(serde_json::from_str::<Thing>("{\"id\":0}").is_err());
+}
+
+#[test]
+fn test_likely_raw_constructed() {
+    let id = Id::new();
+    assert!(!id.likely_raw_constructed());
+    assert!(!id.clone().likely_raw_constructed());
+    // already-allocated seqs are false negatives.
+    assert!(!id_with_seq(id.seq()).likely_raw_constructed());
+    assert!(!id_with_seq(1).likely_raw_constructed());
+    // Other tests may allocate concurrently, but not this many.
+    let next = Id::peek_next_seq();
+    assert!(id_with_seq(next + 1_000_000).likely_raw_constructed());
+    assert!(id_with_seq(u64::MAX).likely_raw_constructed());
+    let lazy = Id::lazy();
+    assert!(!lazy.likely_raw_constructed());
+    assert!(lazy.try_deref().is_none());
+    let _ = lazy.get();
+    assert!(!lazy.likely_raw_constructed());
+}
+
+#[test]
+fn test_shard_hash() {
+    let count = if cfg!(miri) { 3200 } else { 16000 };
+    for &shards in &[2u32, 3, 7, 16] {
+        let mut buckets = vec![0usize; shards as usize];
+        for _ in 0..count {
+            buckets[(Id::new().shard_hash() % shards) as usize] += 1;
+        }
+        let expected = count / shards as usize;
+        for b in &buckets {
+            // within 25% either way
+            assert!(
+                *b > expected * 3 / 4 && *b < expected * 5 / 4,
+                "{:?}",
+                buckets
+            );
+        }
+    }
+    let id = Id::from_raw_integer(core::num::NonZeroU64::new(12345).unwrap());
+    assert_eq!(id.shard_hash(), id.clone().shard_hash());
+}
+
+#[test]
+fn test_into_inner() {
+    let a = Id::new();
+    let v = a.get();
+    let back = Id::from_atomic(a.into_inner());
+    assert_eq!(back, v);
+    let lazy = Id::from_atomic(Id::lazy().into_inner());
+    assert!(lazy.try_deref().is_none());
+    assert_ne!(lazy.get(), 0);
+    let raw = Id::from_atomic(core::sync::atomic::AtomicU64::new(400));
+    assert_eq!(raw.into_inner().into_inner(), 400);
+}
+
+#[test]
+fn test_fmt_alternate() {
+    let id = Id::from_raw_integer(core::num::NonZeroU64::new(123).unwrap());
+    let seq = 123u64.wrapping_mul(0x1337_fe4415);
+    assert_eq!(format!("{:?}", id), format!("Id(0x7b; seq={})", seq));
+    assert_eq!(
+        format!("{:#?}", id),
+        format!(
+            "Id {{\n    value_dec: 123,\n    value_hex: 0x7b,\n    seq: {},\n}}",
+            seq
+        )
+    );
+    // nested in other `{:#?}` output.
+    let nested = format!("{:#?}", Some(&id));
+    assert!(nested.contains("        value_hex: 0x7b,\n"), "{}", nested);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_id_set() {
+    use lazy_id::IdSet;
+    let ids: Vec<Id> = (0..300).map(|_| Id::new()).collect();
+    let mut set = IdSet::default();
+    for id in ids.iter().step_by(2) {
+        assert!(set.insert(id));
+    }
+    assert_eq!(set.len(), 150);
+    for (i, id) in ids.iter().enumerate() {
+        assert_eq!(set.contains(id), i % 2 == 0);
+        assert!(!set.insert(id) || i % 2 == 1);
+    }
+    assert_eq!(set.len(), 300);
+    let listed: Vec<Id> = set.iter().collect();
+    assert!(listed.windows(2).all(|w| w[0].seq() < w[1].seq()));
+    assert_eq!(
+        lazy_id::sorted_by_seq(&ids)[..],
+        listed[listed.len() - 300..]
+    );
+    for id in &ids {
+        assert!(set.remove(id));
+        assert!(!set.remove(id));
+    }
+    assert!(set.is_empty());
+    let raw = Id::try_from_raw(400).unwrap();
+    assert!(!set.contains(&raw));
+    assert!(!set.remove(&raw));
+
+    // Equality is by members, not by capacity or maximum seq.
+    assert_eq!(set, IdSet::new());
+    assert_eq!(set, IdSet::with_max_seq(10));
+    let mut a = IdSet::new();
+    let mut b = IdSet::with_max_seq(1 << 30);
+    a.insert(&ids[0]);
+    assert_ne!(a, b);
+    b.insert(&ids[299]);
+    b.insert(&ids[0]);
+    assert_ne!(a, b);
+    b.remove(&ids[299]);
+    assert_eq!(a, b);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+#[should_panic]
+fn test_id_set_guard() {
+    let mut set = lazy_id::IdSet::with_max_seq(1000);
+    set.insert(&Id::try_from_raw(400).unwrap());
+}
+
+#[test]
+fn test_is_older_than() {
+    let ids: Vec<Id> = (0..50).map(|_| Id::new()).collect();
+    for (i, a) in ids.iter().enumerate() {
+        for (j, b) in ids.iter().enumerate() {
+            assert_eq!(a.is_older_than(b), i < j);
+        }
+    }
+    let lazy = Id::lazy();
+    let later = Id::new();
+    assert!(later.is_older_than(&lazy));
+    assert!(ids[49].is_older_than(&later));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_new_after() {
+    use std::sync::Arc;
+    let root = Arc::new(Id::lazy());
+    assert_eq!(lazy_id::parent_seq_of(&Id::new()), None);
+    let handles = (0..8)
+        .map(|_| {
+            let root = root.clone();
+            std::thread::spawn(move || {
+                let mut chain = vec![root.new_after()];
+                for _ in 0..50 {
+                    let next = chain.last().unwrap().new_after();
+                    chain.push(next);
+                }
+                chain
+            })
+        })
+        .collect::<Vec<_>>();
+    let chains: Vec<Vec<Id>> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+    let mut seen = std::collections::HashSet::new();
+    for chain in &chains {
+        assert_eq!(lazy_id::parent_seq_of(&chain[0]), Some(root.seq()));
+        for pair in chain.windows(2) {
+            assert_eq!(lazy_id::parent_seq_of(&pair[1]), Some(pair[0].seq()));
+        }
+        for id in chain {
+            assert!(seen.insert(id.get()));
+        }
+    }
+    assert_eq!(lazy_id::parent_seq_of(&root), None);
+}
+
+#[test]
+fn test_lazy_id_array() {
+    use lazy_id::LazyIdArray;
+    #[derive(Default)]
+    struct Slots {
+        ids: LazyIdArray<40>,
+    }
+    let mut slots = Slots::default();
+    assert!(slots.ids.iter().all(|id| id.try_deref().is_none()));
+    let mut seen = std::collections::HashSet::new();
+    for i in 0..40 {
+        assert!(seen.insert(slots.ids[i].get()));
+    }
+    assert_eq!(slots.ids[..].len(), 40);
+    assert_eq!(
+        slots.ids[1..3],
+        [slots.ids[1].clone(), slots.ids[2].clone()]
+    );
+    slots.ids[0] = Id::from_raw_integer(core::num::NonZeroU64::new(5).unwrap());
+    assert_eq!(slots.ids[0], 5);
+    let other = Slots::default();
+    assert!(other.ids.iter().all(|id| !seen.contains(&id.get())));
+}
+
+#[test]
+fn test_try_get_ref() {
+    let a = Id::new();
+    assert_eq!(a.try_get_ref(), Ok(a.as_u64_ref()));
+    let lazy = Id::lazy();
+    assert_eq!(lazy.try_get_ref(), Ok(&lazy.get()));
+    let clobbered = Id::try_from_raw(0xdead_beef_dead_beef).unwrap();
+    if cfg!(feature = "checked") {
+        assert_eq!(clobbered.try_get_ref(), Err(lazy_id::IdError::Unallocated));
+    } else {
+        assert_eq!(clobbered.try_get_ref(), Ok(&0xdead_beef_dead_beef));
+    }
+}
+
+#[test]
+fn test_atomic_id_compare_exchange() {
+    use core::sync::atomic::Ordering::{AcqRel, Acquire};
+    use lazy_id::AtomicId;
+    use std::sync::Arc;
+    let cell = AtomicId::empty();
+    let lazy = Id::lazy();
+    let a = Id::new();
+    assert_eq!(
+        cell.compare_exchange(Some(&lazy), &a, AcqRel, Acquire),
+        Err(None)
+    );
+    assert_eq!(
+        cell.compare_exchange(Some(&a), &a, AcqRel, Acquire),
+        Err(None)
+    );
+    assert_eq!(cell.compare_exchange(None, &a, AcqRel, Acquire), Ok(None));
+    assert_eq!(
+        cell.compare_exchange(None, &lazy, AcqRel, Acquire),
+        Err(Some(a.clone()))
+    );
+    // Contended: every thread tries to replace whatever it last saw, and
+    // records its successful swaps. The swaps must form a single chain.
+    let cell = Arc::new(cell);
+    let handles = (0..8)
+        .map(|_| {
+            let cell = cell.clone();
+            std::thread::spawn(move || {
+                let mut won = vec![];
+                let mut seen = cell.load();
+                for _ in 0..200 {
+                    let new = Id::new();
+                    match cell.compare_exchange(seen.as_ref(), &new, AcqRel, Acquire) {
+                        Ok(prev) => {
+                            won.push((prev.unwrap().get(), new.get()));
+                            seen = Some(new);
+                        }
+                        Err(actual) => seen = actual,
+                    }
+                }
+                won
+            })
+        })
+        .collect::<Vec<_>>();
+    let links: std::collections::HashMap<u64, u64> = handles
+        .into_iter()
+        .flat_map(|h| h.join().unwrap())
+        .collect();
+    let mut cur = a.get();
+    let mut steps = 0;
+    while let Some(&next) = links.get(&cur) {
+        cur = next;
+        steps += 1;
+    }
+    assert_eq!(steps, links.len());
+    assert_eq!(cell.load().unwrap(), cur);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_id_collector() {
+    use lazy_id::IdCollector;
+    let allocated: Vec<Id> = (0..100).map(|_| Id::new()).collect();
+    let raw: Vec<Id> = (1..=20u64)
+        .map(|i| Id::try_from_raw(i.wrapping_mul(0x9e37_79b9_7f4a_7c15)).unwrap())
+        .collect();
+    let mut c: IdCollector = allocated.iter().cloned().collect();
+    assert_eq!(c.len(), 100);
+    c.extend(raw.iter().cloned());
+    c.extend(allocated.iter().rev().cloned());
+    c.extend(raw.iter().cloned());
+    // A lazy `Id` is only initialized when it's added, so it's unique.
+    assert!(c.insert(Id::lazy()));
+    assert!(!c.insert(allocated[3].clone()));
+    assert_eq!(c.len(), 121);
+    assert_eq!(c.as_slice()[..100], allocated[..]);
+    assert_eq!(c.as_slice()[100..120], raw[..]);
+    assert!(!c.is_empty());
+    assert_eq!(c.into_vec().len(), 121);
+    assert!(IdCollector::default().is_empty());
+}
+
+#[test]
+fn test_id_handle() {
+    use lazy_id::IdHandle;
+    extern "C" fn echo(h: IdHandle) -> IdHandle {
+        h
+    }
+    extern "C" fn as_u64(h: IdHandle) -> u64 {
+        h.into()
+    }
+    assert_eq!(core::mem::size_of::<IdHandle>(), 8);
+    assert_eq!(
+        core::mem::align_of::<IdHandle>(),
+        core::mem::align_of::<u64>()
+    );
+    let id = Id::lazy();
+    let h = echo(id.to_handle());
+    assert!(id.try_deref().is_some());
+    assert_eq!(as_u64(h), id.get());
+    assert_eq!(h.to_id().unwrap(), id);
+    assert_eq!(IdHandle::from(id.get()), h);
+    assert_eq!(IdHandle::from_u64(0).to_id(), None);
+    // `transmute`-compatible with `u64`, which is what C sees.
+    let raw: u64 = unsafe { core::mem::transmute(h) };
+    assert_eq!(raw, id.get());
+}
+
+#[test]
+fn test_uniqueness_stress() {
+    use std::collections::HashSet;
+    use std::sync::{Arc, Barrier};
+    let (threads, per_thread) = if cfg!(miri) { (4, 50) } else { (16, 20_000) };
+    // Every thread allocates its own ids, and also races with the others to
+    // initialize a shared set of lazy ids.
+    let shared: Arc<Vec<Id>> = Arc::new((0..100).map(|_| Id::lazy()).collect());
+    let barrier = Arc::new(Barrier::new(threads));
+    let handles = (0..threads)
+        .map(|t| {
+            let shared = shared.clone();
+            let barrier = barrier.clone();
+            std::thread::spawn(move || {
+                barrier.wait();
+                let mut own = Vec::with_capacity(per_thread);
+                for i in 0..per_thread {
+                    let id = if i % 2 == 0 { Id::new() } else { Id::lazy() };
+                    own.push(id.get());
+                }
+                // Start at different offsets, so that different threads win
+                // different races.
+                let n = shared.len();
+                let mut seen = vec![0; n];
+                for i in 0..n {
+                    let j = (i + t * 7) % n;
+                    seen[j] = shared[j].get();
+                }
+                (own, seen)
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut all = HashSet::new();
+    for h in handles {
+        let (own, seen) = h.join().unwrap();
+        for v in own {
+            assert!(all.insert(v), "duplicate id {:#x}", v);
+        }
+        // Every thread saw the same value for each shared id.
+        let expected: Vec<u64> = shared.iter().map(Id::get).collect();
+        assert_eq!(seen, expected);
+    }
+    assert_eq!(all.len(), threads * per_thread);
+    for id in shared.iter() {
+        assert!(all.insert(id.get()));
+    }
+}
+
+#[test]
+fn test_assert_count_fits() {
+    const _: () = Id::assert_count_fits(0);
+    const _: () = Id::assert_count_fits(Id::MAX_COUNT);
+    Id::assert_count_fits(1000);
+    assert!(std::panic::catch_unwind(|| Id::assert_count_fits(Id::MAX_COUNT + 1)).is_err());
+}
+
+#[test]
+fn test_downgrade_to_u32() {
+    let a = Id::lazy();
+    let small = a.downgrade_to_u32().unwrap();
+    assert_eq!(small as u64, a.seq());
+    assert_eq!(Id::upgrade_from_u32(small).unwrap(), a);
+    assert_eq!(Id::upgrade_from_u32(0), None);
+
+    let max = id_with_seq(u32::MAX as u64);
+    assert_eq!(max.downgrade_to_u32(), Some(u32::MAX));
+    assert_eq!(Id::upgrade_from_u32(u32::MAX).unwrap(), max);
+    let over = id_with_seq(u32::MAX as u64 + 1);
+    assert_eq!(over.downgrade_to_u32(), None);
+    assert_eq!(id_with_seq(u64::MAX).downgrade_to_u32(), None);
+    assert_eq!(id_with_seq(1).downgrade_to_u32(), Some(1));
+}
+
+#[test]
+fn test_seq_key() {
+    use lazy_id::SeqKey;
+    use std::collections::BTreeMap;
+    // Lazy ids, initialized in reverse, so creation order isn't index order.
+    let ids: Vec<Id> = (0..20).map(|_| Id::lazy()).collect();
+    for id in ids.iter().rev() {
+        let _ = id.get();
+    }
+    #[allow(clippy::mutable_key_type)]
+    let map: BTreeMap<SeqKey, usize> = ids
+        .iter()
+        .enumerate()
+        .map(|(i, id)| (SeqKey::from(id.clone()), i))
+        .collect();
+    let order: Vec<usize> = map.values().cloned().collect();
+    assert_eq!(order, (0..20).rev().collect::<Vec<_>>());
+    let created_after_10: Vec<usize> = map
+        .range(SeqKey(ids[10].clone())..)
+        .map(|(_, &i)| i)
+        .collect();
+    assert_eq!(created_after_10, (0..=10).rev().collect::<Vec<_>>());
+    assert_eq!(map[&SeqKey(ids[4].clone())], 4);
+    assert_eq!(
+        SeqKey(ids[0].clone()),
+        SeqKey(Id::from_raw_integer(ids[0].get_nonzero()))
+    );
+    let back: Id = SeqKey(ids[5].clone()).into();
+    assert_eq!(back, ids[5]);
+    assert_eq!(SeqKey(ids[6].clone()).as_ref(), &ids[6]);
+}
+
+#[test]
+fn test_reset_to_lazy() {
+    let mut pool: Vec<Id> = (0..10).map(|_| Id::new()).collect();
+    let old: Vec<u64> = pool.iter().map(Id::get).collect();
+    for id in &mut pool {
+        id.reset_to_lazy();
+        assert!(id.try_deref().is_none());
+        // Resetting an uninitialized id is fine too.
+        id.reset_to_lazy();
+    }
+    let next = Id::new();
+    for (id, old) in pool.iter().zip(&old) {
+        assert_ne!(id.get(), *old);
+        assert!(next.is_older_than(id));
+    }
+}
+
+#[test]
+fn test_from_debug_str() {
+    use lazy_id::IdError;
+    for id in (0..20).map(|_| Id::lazy()) {
+        assert_eq!(Id::from_debug_str(&format!("{:?}", id)).unwrap(), id);
+        assert_eq!(
+            Id::from_debug_str(&format!("Id({:#x})", id.get())).unwrap(),
+            id
+        );
+    }
+    let raw = Id::try_from_raw(u64::MAX).unwrap();
+    assert_eq!(Id::from_debug_str(&format!("{:?}", raw)).unwrap(), raw);
+
+    let a = Id::new();
+    let bad_seq = format!("Id({:#x}; seq={})", a.get(), a.seq() + 1);
+    assert_eq!(Id::from_debug_str(&bad_seq), Err(IdError::SeqMismatch));
+    assert_eq!(Id::from_debug_str("Id(0x0)"), Err(IdError::Zero));
+    assert_eq!(Id::from_debug_str("Id(0x0; seq=0)"), Err(IdError::Zero));
+    let malformed = [
+        "",
+        "Id()",
+        "Id(0x)",
+        "Id(0x+1)",
+        "Id(0xg)",
+        "Id(7b)",
+        "id(0x7b)",
+        "Id(0x7b",
+        "Id(0x7b) ",
+        " Id(0x7b)",
+        "Id(0x7b;seq=1)",
+        "Id(0x7b; seq=)",
+        "Id(0x7b; seq=+1)",
+        "Id(0x7b; seq=1; seq=1)",
+        "Id(0x10000000000000000)",
+        "Id { value_dec: 123 }",
+    ];
+    for s in malformed.iter() {
+        assert_eq!(Id::from_debug_str(s), Err(IdError::Parse), "{:?}", s);
+    }
+}
+
+#[test]
+fn test_id_allocator() {
+    use lazy_id::{IdAllocator, ScopedId};
+    use std::collections::HashSet;
+    static A: IdAllocator = IdAllocator::new();
+    static B: IdAllocator = IdAllocator::new();
+    static LAZY_A: ScopedId = ScopedId::lazy(&A);
+    let per_thread = if cfg!(miri) { 100 } else { 50_000 };
+    let handles = (0..4)
+        .map(|t| {
+            std::thread::spawn(move || {
+                let alloc = if t % 2 == 0 { &A } else { &B };
+                let mut ids = Vec::with_capacity(per_thread * 2);
+                for _ in 0..per_thread {
+                    ids.push(Id::new_with_allocator(alloc).get());
+                    ids.push(Id::new().get());
+                }
+                ids.push(LAZY_A.get());
+                ids
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut seen = HashSet::new();
+    let mut lazy_count = 0;
+    for h in handles {
+        for v in h.join().unwrap() {
+            if v == LAZY_A.get() {
+                lazy_count += 1;
+            } else {
+                assert!(seen.insert(v), "duplicate id {:#x}", v);
+            }
+        }
+    }
+    assert_eq!(lazy_count, 4);
+    assert!(seen.insert(LAZY_A.get()));
+    assert!(std::ptr::eq(LAZY_A.allocator(), &A));
+
+    // Within an allocator, order is preserved.
+    // Enough to cross into a new block, except under miri.
+    let count = if cfg!(miri) { 100 } else { 100_000 };
+    let ids: Vec<Id> = (0..count).map(|_| Id::new_with_allocator(&B)).collect();
+    assert!(ids.windows(2).all(|w| w[0].is_older_than(&w[1])));
+    let scoped = ScopedId::lazy(&B);
+    assert!(scoped.clone().is_older_than(&Id::new_with_allocator(&B)));
+    assert_eq!(format!("{:?}", scoped), format!("{:?}", *scoped));
+}
+
+#[test]
+fn test_seq_stats() {
+    let ids: Vec<Id> = [5, 3, 9, 3, 7].iter().map(|&s| id_with_seq(s)).collect();
+    let stats = lazy_id::seq_stats(&ids).unwrap();
+    assert_eq!((stats.min, stats.max, stats.count), (3, 9, 5));
+    assert_eq!(stats.span(), 6);
+    let one = lazy_id::seq_stats(&ids[..1]).unwrap();
+    assert_eq!((one.min, one.max, one.count, one.span()), (5, 5, 1, 0));
+    assert_eq!(lazy_id::seq_stats(&[]), None);
+
+    // Not by value: the values are in a different order than the seqs.
+    let fresh: Vec<Id> = (0..50).map(|_| Id::lazy()).collect();
+    let stats = lazy_id::seq_stats(&fresh).unwrap();
+    assert_eq!(stats.min, fresh[0].seq());
+    assert_eq!(stats.max, fresh[49].seq());
+    assert_eq!(stats.span(), fresh[49].seq() - fresh[0].seq());
+}
+
+#[test]
+fn test_seq_bucket() {
+    assert_eq!(id_with_seq(1000).seq_bucket(1000), 1);
+    assert_eq!(id_with_seq(1999).seq_bucket(1000), 1);
+    assert_eq!(id_with_seq(2000).seq_bucket(1000), 2);
+    assert_eq!(id_with_seq(12345).seq_bucket(1), 12345);
+
+    // Other tests allocate concurrently, so use a bucket too big to straddle.
+    let a = Id::new();
+    let b = Id::new();
+    assert_eq!(a.seq_bucket(1 << 40), b.seq_bucket(1 << 40));
+    assert!(std::panic::catch_unwind(|| a.seq_bucket(0)).is_err());
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_group_by_seq_bucket() {
+    let ids: Vec<Id> = [2500, 1200, 2100, 3000, 1999]
+        .iter()
+        .map(|&s| id_with_seq(s))
+        .collect();
+    let groups = lazy_id::group_by_seq_bucket(&ids, 1000);
+    let expected = vec![
+        (1, vec![id_with_seq(1200), id_with_seq(1999)]),
+        (2, vec![id_with_seq(2500), id_with_seq(2100)]),
+        (3, vec![id_with_seq(3000)]),
+    ];
+    assert_eq!(groups, expected);
+    assert!(lazy_id::group_by_seq_bucket(&[], 10).is_empty());
+    assert!(std::panic::catch_unwind(|| lazy_id::group_by_seq_bucket(&[], 0)).is_err());
+}
+
+#[test]
+fn test_lazy_init_race() {
+    use std::sync::{Arc, Barrier};
+    let (rounds, threads) = if cfg!(miri) { (5, 4) } else { (200, 8) };
+    for _ in 0..rounds {
+        let id = Arc::new(Id::lazy());
+        let barrier = Arc::new(Barrier::new(threads));
+        let handles = (0..threads)
+            .map(|_| {
+                let (id, barrier) = (id.clone(), barrier.clone());
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    id.observe()
+                })
+            })
+            .collect::<Vec<_>>();
+        let results: Vec<(u64, bool)> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        // Exactly one thread won, and the losers all got the winner's value.
+        assert_eq!(results.iter().filter(|r| r.1).count(), 1);
+        assert!(results.iter().all(|r| r.0 == id.get()));
+    }
+}
+
+#[test]
+fn test_lazy_with() {
+    use core::num::NonZeroU64;
+    use lazy_id::LazyWith;
+    use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
+    use std::sync::{Arc, Barrier};
+    static EXTERNAL: AtomicU64 = AtomicU64::new(1);
+    fn provide() -> Result<NonZeroU64, ()> {
+        Ok(NonZeroU64::new(EXTERNAL.fetch_add(1, Relaxed) << 32).unwrap())
+    }
+    let threads = if cfg!(miri) { 2 } else { 8 };
+    for _ in 0..if cfg!(miri) { 2 } else { 50 } {
+        let id = Arc::new(LazyWith::new(provide));
+        let barrier = Arc::new(Barrier::new(threads));
+        let handles = (0..threads)
+            .map(|_| {
+                let (id, barrier) = (id.clone(), barrier.clone());
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    id.try_get().unwrap().get()
+                })
+            })
+            .collect::<Vec<_>>();
+        let seen: Vec<u64> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        let winner = *id.try_deref().unwrap();
+        assert_eq!(winner & 0xffff_ffff, 0);
+        assert!(seen.iter().all(|&v| v == winner));
+    }
+
+    // Failures leave the id uninitialized, and are retried.
+    let fail = AtomicU64::new(2);
+    let flaky = LazyWith::new(|| {
+        if fail.fetch_sub(1, Relaxed) > 0 {
+            Err("not yet")
+        } else {
+            Ok(NonZeroU64::new(5).unwrap())
+        }
+    });
+    assert_eq!(format!("{:?}", flaky), "LazyWith(<uninit>)");
+    assert_eq!(flaky.try_get().unwrap_err(), "not yet");
+    assert_eq!(flaky.try_get().unwrap_err(), "not yet");
+    assert!(flaky.try_deref().is_none());
+    assert_eq!(flaky.try_get().unwrap(), 5);
+    assert_eq!(flaky.try_get().unwrap(), 5);
+    assert!(format!("{:?}", flaky).starts_with("LazyWith(Id(0x5;"));
+}
+
+#[test]
+fn test_hash_value() {
+    use core::hash::{Hash, Hasher};
+    use lazy_id::EpochId;
+    // Records every write, so we can check exactly what each impl writes.
+    #[derive(Default)]
+    struct Recorder(Vec<u64>);
+    impl Hasher for Recorder {
+        fn finish(&self) -> u64 {
+            0
+        }
+        fn write(&mut self, _: &[u8]) {
+            panic!("unexpected `write`");
+        }
+        fn write_u64(&mut self, v: u64) {
+            self.0.push(v);
+        }
+    }
+    fn writes<T: Hash>(v: &T) -> Vec<u64> {
+        let mut r = Recorder::default();
+        v.hash(&mut r);
+        r.0
+    }
+    let a = Id::lazy();
+    assert_eq!(writes(&a), [a.hash_value()]);
+    assert_eq!(writes(&a.freeze()), [a.hash_value()]);
+    let e = EpochId::new();
+    assert_eq!(writes(&e), [e.hash_value()]);
+    // These are documented as stable.
+    assert_eq!(
+        Id::try_from_raw(0x1234_5678_9abc_def0)
+            .unwrap()
+            .hash_value(),
+        0x1234_5678_9abc_def0
+    );
+    assert_eq!(Id::try_from_raw(1).unwrap().freeze().hash_value(), 1);
+    assert_eq!(e.hash_value(), e.get());
+}
+
+// Small enough to run under miri (which the other stress tests only do with
+// reduced counts), and touches each of the `unsafe` blocks reachable from
+// `Id`'s API: the lazy init race, the `&u64` casts, `ensure_init`, and so on.
+#[test]
+fn test_unsafe_surface() {
+    use std::sync::{Arc, Barrier};
+    let threads = if cfg!(miri) { 3 } else { 8 };
+    let ids: Arc<Vec<Id>> = Arc::new((0..4).map(|_| Id::lazy()).collect());
+    let barrier = Arc::new(Barrier::new(threads));
+    let handles = (0..threads)
+        .map(|_| {
+            let (ids, barrier) = (ids.clone(), barrier.clone());
+            std::thread::spawn(move || {
+                barrier.wait();
+                // Both the `get()` race (and `new_unchecked` for the loser),
+                // and references to the value while others may be racing on
+                // other elements.
+                let refs: Vec<&u64> = ids.iter().map(Id::as_u64_ref).collect();
+                let derefs: Vec<u64> = ids.iter().map(|id| **id).collect();
+                assert_eq!(refs.into_iter().cloned().collect::<Vec<_>>(), derefs);
+                derefs
+            })
+        })
+        .collect::<Vec<_>>();
+    let expected: Vec<u64> = ids.iter().map(Id::get).collect();
+    for h in handles {
+        assert_eq!(h.join().unwrap(), expected);
+    }
+
+    let raw = Id::from_raw_integer(core::num::NonZeroU64::new(7).unwrap());
+    assert_eq!(*raw.as_u64_ref(), 7);
+    assert_eq!(raw.try_deref(), Some(&7));
+
+    // `ensure_init`, by value and through `&mut`.
+    let mut lazy = Id::lazy();
+    let v = u64::from(&mut lazy);
+    assert_eq!(lazy.try_deref(), Some(&v));
+    assert_eq!(u64::from(lazy), v);
+    assert_ne!(core::num::NonZeroU64::from(Id::lazy()).get(), v);
+
+    let handle = Id::lazy().to_handle();
+    assert_eq!(handle.to_id().unwrap().get(), handle.get());
+    static ALLOC: lazy_id::IdAllocator = lazy_id::IdAllocator::new();
+    assert_ne!(
+        Id::new_with_allocator(&ALLOC),
+        Id::new_with_allocator(&ALLOC)
+    );
+}
+
+#[test]
+fn test_as_ptr() {
+    use core::sync::atomic::{AtomicU64, Ordering::Acquire};
+    let a = Id::lazy();
+    let p = a.as_ptr();
+    // Doesn't initialize, and reads as zero until initialized.
+    assert!(a.try_deref().is_none());
+    // SAFETY: `Id` has the same layout as `AtomicU64`, and this is an atomic
+    // load.
+    let atomic = unsafe { &*(p as *const AtomicU64) };
+    assert_eq!(atomic.load(Acquire), 0);
+    let v = a.get();
+    assert_eq!(atomic.load(Acquire), v);
+    // SAFETY: initialized, and no other threads have access to `a`.
+    assert_eq!(unsafe { p.read() }, v);
+    assert_eq!(p, a.as_u64_ref() as *const u64);
+}
+
+#[test]
+fn test_as_id() {
+    use core::num::NonZeroU64;
+    use lazy_id::{AsId, EpochId};
+    fn register(x: impl AsId) -> u64 {
+        x.as_id()
+    }
+    fn register_all<T: AsId>(xs: &[T]) -> Vec<u64> {
+        xs.iter().map(AsId::as_id).collect()
+    }
+    let lazy = Id::lazy();
+    let v = register(&lazy);
+    assert!(lazy.try_deref().is_some());
+    assert_ne!(v, 0);
+    assert_eq!(register(v), v);
+    assert_eq!(register(0u64), 0);
+    assert_eq!(register(NonZeroU64::new(v).unwrap()), v);
+    assert_eq!(register(lazy.freeze()), v);
+    let e = EpochId::new();
+    assert_eq!(register(e), e.get());
+    assert_eq!(register(Id::lazy()).min(1), 1);
+    let ids = vec![Id::new(), Id::lazy()];
+    assert_eq!(register_all(&ids), [ids[0].get(), ids[1].get()]);
+}
+
+#[test]
+fn test_try_reserve() {
+    let before = Id::new();
+    let block = Id::try_reserve(100).unwrap();
+    assert_eq!(block.len(), 100);
+    let ids: Vec<Id> = block.collect();
+    let after = Id::new();
+    assert!(before.is_older_than(&ids[0]));
+    assert!(ids[99].is_older_than(&after));
+    for w in ids.windows(2) {
+        assert_eq!(w[0].seq() + 1, w[1].seq());
+    }
+    assert!(ids.iter().all(|id| !id.likely_raw_constructed()));
+    assert_eq!(Id::try_reserve(0).unwrap().count(), 0);
+    let next = Id::peek_next_seq();
+    assert_eq!(
+        Id::try_reserve(1 << 63).err(),
+        Some(lazy_id::IdError::Overflow)
+    );
+    assert!(Id::peek_next_seq() < (1 << 62) && Id::peek_next_seq() >= next);
+}
+
+#[test]
+fn test_verify_constants() {
+    const _: [(); 1] = [(); Id::verify_constants() as usize];
+    assert!(Id::verify_constants());
+}
+
+#[test]
+fn test_byte_array() {
+    let a = Id::lazy();
+    let bytes: [u8; 8] = a.clone().into();
+    assert_eq!(bytes, a.get().to_ne_bytes());
+    assert_eq!(Id::from(bytes), a);
+    // By value forces init.
+    let lazy_bytes = <[u8; 8]>::from(Id::lazy());
+    assert_ne!(lazy_bytes, [0; 8]);
+    assert_eq!(Id::from(lazy_bytes).get(), u64::from_ne_bytes(lazy_bytes));
+    // Native-endian, unlike the explicit helpers on `u64`.
+    let v = Id::from(0x0102_0304_0506_0708u64.to_ne_bytes());
+    assert_eq!(v, 0x0102_0304_0506_0708);
+    assert_eq!(v.get().to_le_bytes(), [8, 7, 6, 5, 4, 3, 2, 1]);
+    // All zeros is an uninitialized id.
+    let zero = Id::from([0u8; 8]);
+    assert!(zero.try_deref().is_none());
+    assert_ne!(zero.get(), 0);
+}